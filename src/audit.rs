@@ -0,0 +1,180 @@
+use chrono::NaiveDateTime;
+
+use crate::{now, RepoValue, SqlUpdates, SqlValues, Table};
+
+/// A column changed by an update, with its values before and after.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditChange {
+	pub column: String,
+	pub old_value: RepoValue<'static>,
+	pub new_value: RepoValue<'static>,
+}
+
+/// Who changed which columns of which entity, and when.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditRecord {
+	pub entity: &'static str,
+	pub key: String,
+	pub actor: Option<String>,
+	pub changed_at: NaiveDateTime,
+	pub changes: Vec<AuditChange>,
+}
+
+impl AuditRecord {
+	/// reads the old values of the columns assigned by `updates`, before they are applied to `entity`
+	pub fn before<E: Table>(entity: &E, updates: &SqlUpdates<'_, E>, actor: Option<&str>) -> Self {
+		let changes = updates.assignments()
+			.map(|(field, _)| AuditChange {
+				column: field.to_string(),
+				old_value: entity.field_value(field).unwrap_or(RepoValue::Null),
+				new_value: RepoValue::Null,
+			})
+			.collect();
+		Self {
+			entity: E::ENTITY_NAME,
+			key: format!("{:?}", entity.get_key()),
+			actor: actor.map(String::from),
+			changed_at: now(),
+			changes,
+		}
+	}
+
+	/// reads the new values, once the updates have been applied to `entity`.
+	/// The columns set by the database, like `DEFAULT`, keep the value of the entity.
+	pub fn after<E: Table>(mut self, entity: &E) -> Self {
+		for change in self.changes.iter_mut() {
+			change.new_value = entity.field_value(&change.column).unwrap_or(RepoValue::Null);
+		}
+		self
+	}
+
+	/// one row per change, for an audit table of the columns
+	/// `entity`, `entity_key`, `column_name`, `old_value`, `new_value`, `actor` and `changed_at`
+	pub fn rows(&self) -> Vec<SqlValues<'_>> {
+		self.changes.iter()
+			.map(|change| {
+				SqlValues::default()
+					.with("entity", self.entity)
+					.with("entity_key", self.key.as_str())
+					.with("column_name", change.column.as_str())
+					.with("old_value", change.old_value.clone())
+					.with("new_value", change.new_value.clone())
+					.with("actor", self.actor.as_deref())
+					.with("changed_at", self.changed_at)
+			})
+			.collect()
+	}
+}
+
+/// Receives the audit records of the updates, e.g. to send them to a log.
+pub trait AuditSink: Send + Sync {
+	fn record(&self, record: AuditRecord);
+}
+
+#[derive(Clone, Copy)]
+pub enum AuditDestination<'a> {
+	/// inserts the records into the table, with the same connection or transaction as the update
+	Table(&'a str),
+	Sink(&'a dyn AuditSink),
+}
+
+/// Where the audit records of an update go, and who is making it.
+#[derive(Clone, Copy)]
+pub struct Audit<'a> {
+	destination: AuditDestination<'a>,
+	actor: Option<&'a str>,
+}
+
+impl<'a> Audit<'a> {
+	pub fn to_table(table: &'a str) -> Self {
+		Self { destination: AuditDestination::Table(table), actor: None }
+	}
+
+	pub fn to_sink(sink: &'a dyn AuditSink) -> Self {
+		Self { destination: AuditDestination::Sink(sink), actor: None }
+	}
+
+	pub fn by(mut self, actor: &'a str) -> Self {
+		self.actor = Some(actor);
+		self
+	}
+
+	pub fn destination(&self) -> AuditDestination<'a> {
+		self.destination
+	}
+
+	pub fn actor(&self) -> Option<&'a str> {
+		self.actor
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::NaiveDate;
+
+	use crate::{repo_entity, set_thread_clock, FixedClock};
+	use super::*;
+
+	repo_entity!(
+		#[derive(Debug, Clone, PartialEq)]
+		#[table_name = "accounts"]
+		struct Account {
+			keys { id: u64 },
+			data { owner: String, balance: i64 }
+		}
+	);
+
+	fn set_balance(balance: i64) -> SqlUpdates<'static, Account> {
+		let mut updates = SqlUpdates::default();
+		updates.push("balance", balance, move |account: &mut Account| account.balance = balance);
+		updates
+	}
+
+	#[test]
+	fn records_hold_the_values_before_and_after_the_update() {
+		let noon = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+		let _clock = set_thread_clock(FixedClock(noon));
+		let mut account = Account { id: 7, owner: "ann".to_string(), balance: 10 };
+		let updates = set_balance(25);
+
+		let record = AuditRecord::before(&account, &updates, Some("bob"));
+		updates.apply(&mut account);
+		let record = record.after(&account);
+
+		assert_eq!(record, AuditRecord {
+			entity: "Account",
+			key: "7".to_string(),
+			actor: Some("bob".to_string()),
+			changed_at: noon,
+			changes: vec![AuditChange { column: "balance".to_string(), old_value: RepoValue::Int(10), new_value: RepoValue::Int(25) }],
+		});
+	}
+
+	#[test]
+	fn each_change_is_a_row() {
+		let account = Account { id: 7, owner: "ann".to_string(), balance: 10 };
+		let mut updates = set_balance(25);
+		updates.push("owner", "eve", |account: &mut Account| account.owner = "eve".to_string());
+
+		let record = AuditRecord::before(&account, &updates, None);
+		let rows = record.rows();
+		assert_eq!(rows.len(), 2);
+		let columns = rows[1].iter().map(|(field, _)| *field).collect::<Vec<_>>();
+		assert_eq!(columns, ["entity", "entity_key", "column_name", "old_value", "new_value", "actor", "changed_at"]);
+		assert!(rows[1].expressions().starts_with("entity='Account', entity_key='7', column_name='owner', old_value='ann', new_value=NULL, actor=NULL"));
+	}
+
+	#[test]
+	fn audits_name_their_destination_and_actor() {
+		struct Discard;
+		impl AuditSink for Discard {
+			fn record(&self, _: AuditRecord) {}
+		}
+
+		let audit = Audit::to_table("audit_log").by("bob");
+		assert!(matches!(audit.destination(), AuditDestination::Table("audit_log")));
+		assert_eq!(audit.actor(), Some("bob"));
+		assert!(matches!(Audit::to_sink(&Discard).destination(), AuditDestination::Sink(_)));
+		assert_eq!(Audit::to_sink(&Discard).actor(), None);
+	}
+}
@@ -0,0 +1,176 @@
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use chrono::{Duration, NaiveDateTime, Utc};
+
+/// The source of the timestamps set by the generated code, like `created_at` and `updated_at`.
+pub trait Clock: Send + Sync {
+	fn now(&self) -> NaiveDateTime;
+}
+
+/// The current UTC time of the system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+	fn now(&self) -> NaiveDateTime {
+		Utc::now().naive_utc()
+	}
+}
+
+/// Always the same time, to freeze the time in tests.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock(pub NaiveDateTime);
+
+impl Clock for FixedClock {
+	fn now(&self) -> NaiveDateTime {
+		self.0
+	}
+}
+
+/// Starts at `start` and advances by `step` on every read, so that tests get distinct and predictable times.
+#[derive(Debug)]
+pub struct SteppingClock {
+	start: NaiveDateTime,
+	step: Duration,
+	ticks: AtomicI32,
+}
+
+impl SteppingClock {
+	pub fn new(start: NaiveDateTime, step: Duration) -> Self {
+		Self { start, step, ticks: AtomicI32::new(0) }
+	}
+}
+
+impl Clock for SteppingClock {
+	fn now(&self) -> NaiveDateTime {
+		let ticks = self.ticks.fetch_add(1, Ordering::Relaxed);
+		self.start + self.step * ticks
+	}
+}
+
+static CLOCK: RwLock<Option<Arc<dyn Clock>>> = RwLock::new(None);
+
+thread_local! {
+	/// the clocks set on the thread, the last one being the current clock
+	static THREAD_CLOCKS: RefCell<Vec<Arc<dyn Clock>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// replaces the clock of the whole process, which is `SystemClock` until then
+pub fn set_clock<C: Clock + 'static>(clock: C) {
+	*CLOCK.write().unwrap() = Some(Arc::new(clock));
+}
+
+/// restores `SystemClock`
+pub fn reset_clock() {
+	*CLOCK.write().unwrap() = None;
+}
+
+/// replaces the clock of the current thread, ahead of the clock of the process, until the guard is dropped.
+/// Tests running in parallel thereby each freeze their own time; an async test must run on a single thread,
+/// see `ClockGuard`.
+pub fn set_thread_clock<C: Clock + 'static>(clock: C) -> ClockGuard {
+	let clock: Arc<dyn Clock> = Arc::new(clock);
+	THREAD_CLOCKS.with(|clocks| clocks.borrow_mut().push(clock.clone()));
+	ClockGuard { clock, _not_send: PhantomData }
+}
+
+/// runs `f` with `clock` as the clock of the current thread
+pub fn with_clock<C: Clock + 'static, R>(clock: C, f: impl FnOnce() -> R) -> R {
+	let _guard = set_thread_clock(clock);
+	f()
+}
+
+/// Removes its clock from the clocks of the thread when dropped, which restores the previous clock if it was the current one.
+/// Guards may be dropped in any order: the current clock is always the one of the latest guard still alive.
+///
+/// The clock belongs to the thread, not to the task: a tokio task moved to another worker thread of a multi-threaded
+/// runtime no longer sees it. The guard is therefore not `Send`, so that it cannot be held across an `.await` of such a task,
+/// and async tests setting a thread clock must use the current-thread runtime.
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<repo_helper::ClockGuard>();
+/// ```
+#[must_use = "the clock of the thread is restored as soon as the guard is dropped"]
+pub struct ClockGuard {
+	clock: Arc<dyn Clock>,
+	_not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ClockGuard {
+	fn drop(&mut self) {
+		THREAD_CLOCKS.with(|clocks| {
+			let mut clocks = clocks.borrow_mut();
+			if let Some(position) = clocks.iter().rposition(|clock| Arc::ptr_eq(clock, &self.clock)) {
+				clocks.remove(position);
+			}
+		});
+	}
+}
+
+/// returns the time of the clock of the current thread if any, or else of the clock of the process
+pub fn now() -> NaiveDateTime {
+	if let Some(now) = THREAD_CLOCKS.with(|clocks| clocks.borrow().last().map(|clock| clock.now())) {
+		return now;
+	}
+	match CLOCK.read().unwrap().as_ref() {
+		Some(clock) => clock.now(),
+		None => SystemClock.now(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::NaiveDate;
+
+	use super::*;
+
+	fn at(hour: u32) -> NaiveDateTime {
+		NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+	}
+
+	#[test]
+	fn thread_clock_is_restored_by_the_guard() {
+		let outer = set_thread_clock(FixedClock(at(8)));
+		assert_eq!(now(), at(8));
+		{
+			let _inner = set_thread_clock(FixedClock(at(9)));
+			assert_eq!(now(), at(9));
+		}
+		assert_eq!(now(), at(8));
+		drop(outer);
+		assert!(now() > at(9));
+	}
+
+	#[test]
+	fn guards_dropped_out_of_order_keep_the_latest_clock() {
+		let outer = set_thread_clock(FixedClock(at(8)));
+		let inner = set_thread_clock(FixedClock(at(9)));
+		drop(outer);
+		assert_eq!(now(), at(9));
+		drop(inner);
+		assert!(now() > at(9));
+	}
+
+	#[test]
+	fn thread_clocks_are_independent() {
+		let other = std::thread::spawn(|| with_clock(FixedClock(at(1)), || {
+			std::thread::sleep(std::time::Duration::from_millis(20));
+			now()
+		}));
+		let here = with_clock(FixedClock(at(2)), now);
+		assert_eq!(here, at(2));
+		assert_eq!(other.join().unwrap(), at(1));
+	}
+
+	#[test]
+	fn stepping_clock_advances_on_every_read() {
+		let clock = SteppingClock::new(at(0), Duration::seconds(1));
+		assert_eq!(clock.now(), at(0));
+		assert_eq!(clock.now(), at(0) + Duration::seconds(1));
+		assert_eq!(clock.now(), at(0) + Duration::seconds(2));
+	}
+}
@@ -1,12 +1,26 @@
-#[derive(Debug, PartialEq, Clone)]
+/// Exhaustive matches on `Definable` must handle `DbDefault`, which is new since 0.1.0 (see CHANGELOG.md).
+#[derive(Debug, PartialEq, Clone, Default)]
 pub enum Definable<T> {
 	Defined(T),
+	/// set to the `DEFAULT` of the column, which is only known to the database
+	DbDefault,
+	#[default]
 	Undefined,
 }
 
-impl<T> Default for Definable<T> {
-	fn default() -> Self {
-		Self::Undefined
+/// Types which can hold SQL `NULL`
+///
+/// Generated methods which only make sense for some field types, such as the `_null` setters of partials,
+/// bound the field type with a higher-ranked `where for<'x> T: Nullable`. A bound without any generic
+/// parameter is checked when the struct is defined, so it would reject every struct with a non-nullable field;
+/// the unused lifetime turns it into a bound which is only checked when the method is called.
+pub trait Nullable {
+	fn null() -> Self;
+}
+
+impl<T> Nullable for Option<T> {
+	fn null() -> Self {
+		None
 	}
 }
 
@@ -19,10 +33,13 @@ impl<T> Definable<T> {
 	}
 	pub fn is_defined_and(&self, f: impl FnOnce(&T) -> bool) -> bool {
 		match self {
-			Self::Undefined => false,
+			Self::Undefined | Self::DbDefault => false,
 			Self::Defined(x) => f(x),
 		}
 	}
+	pub const fn is_db_default(&self) -> bool {
+		matches!(self, Definable::DbDefault)
+	}
 
 	pub const fn is_none(&self) -> bool {
 		self.is_undefined()
@@ -38,6 +55,7 @@ impl<T> Definable<T> {
 	where F: FnOnce(T) -> U {
 		match self {
 			Self::Defined(x) => Definable::Defined(f(x)),
+			Self::DbDefault => Definable::DbDefault,
 			Self::Undefined => Definable::Undefined,
 		}
 	}
@@ -45,19 +63,21 @@ impl<T> Definable<T> {
 	pub const fn as_option(&self) -> Option<&T> {
 		match *self {
 			Self::Defined(ref x) => Some(x),
-			Self::Undefined => None,
+			Self::DbDefault | Self::Undefined => None,
 		}
 	}
 
 	pub const fn as_ref(&self) -> Definable<&T> {
 		match *self {
 			Self::Defined(ref x) => Definable::Defined(x),
+			Self::DbDefault => Definable::DbDefault,
 			Self::Undefined => Definable::Undefined,
 		}
 	}
 	pub fn as_mut(&mut self) -> Definable<&mut T> {
 		match *self {
 			Self::Defined(ref mut x) => Definable::Defined(x),
+			Self::DbDefault => Definable::DbDefault,
 			Self::Undefined => Definable::Undefined,
 		}
 	}
@@ -66,21 +86,65 @@ impl<T> Definable<T> {
 		*self = Definable::Defined(value);
 	}
 
+	pub fn set_default(&mut self) {
+		*self = Definable::DbDefault;
+	}
+
+	/// returns the defined value, leaving `Undefined` behind; `DbDefault` has no value and yields `None`
 	pub fn take(&mut self) -> Option<T> {
 		match std::mem::take(self) {
 			Definable::Defined(value) => Some(value),
-			Definable::Undefined => None,
+			Definable::DbDefault | Definable::Undefined => None,
 		}
 	}
 }
 
+impl<T: Nullable> Definable<T> {
+	pub fn set_null(&mut self) {
+		self.set(T::null());
+	}
+}
+
 impl<T: Copy> Copy for Definable<T> {}
 
 impl<T: Copy> Definable<T> {
 	pub fn get(&self) -> Option<T> {
 		match *self {
 			Definable::Defined(value) => Some(value),
-			Definable::Undefined => None,
+			Definable::DbDefault | Definable::Undefined => None,
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn db_default_is_neither_defined_nor_undefined() {
+		let mut value = Definable::<u32>::Undefined;
+		value.set_default();
+		assert!(value.is_db_default());
+		assert!(!value.is_defined() && !value.is_undefined());
+		assert_eq!(value.get(), None);
+		assert_eq!(value.map(|v| v + 1), Definable::DbDefault);
+	}
+
+	#[test]
+	fn take_leaves_undefined_behind() {
+		let mut value = Definable::Defined(3);
+		assert_eq!(value.take(), Some(3));
+		assert!(value.is_undefined());
+
+		let mut value = Definable::<u32>::DbDefault;
+		assert_eq!(value.take(), None);
+		assert!(value.is_undefined());
+	}
+
+	#[test]
+	fn nullable_values_are_set_to_null() {
+		let mut value = Definable::Defined(Some(3));
+		value.set_null();
+		assert_eq!(value, Definable::Defined(None));
+	}
+}
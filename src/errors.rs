@@ -1,19 +1,104 @@
+use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-pub struct EntityNotFoundError<Key: Debug>(&'static str, Key);
+use crate::{ER_LOCK_DEADLOCK, ER_LOCK_WAIT_TIMEOUT, ER_QUERY_TIMEOUT};
+
+/// The key is kept as its `Debug` rendering, so that the error is classified by `RepoError` whatever the type of the key.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EntityNotFoundError {
+	entity: &'static str,
+	key: String,
+}
+
+impl EntityNotFoundError {
+	pub fn new(entity_name: &'static str, key: impl Debug) -> Self {
+		Self { entity: entity_name, key: format!("{:?}", key) }
+	}
+
+	pub fn entity(&self) -> &'static str {
+		self.entity
+	}
+
+	/// the `Debug` rendering of the key
+	pub fn key(&self) -> &str {
+		&self.key
+	}
+}
+impl Display for EntityNotFoundError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "Not found {} {{id={}}})", self.entity, self.key)
+	}
+}
+impl std::error::Error for EntityNotFoundError {}
+
+
+/// The row of the entity was changed by someone else since it was loaded, or was deleted.
+/// Like `EntityNotFoundError`, the key is kept as its `Debug` rendering.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OptimisticLockError {
+	entity: &'static str,
+	key: String,
+}
+
+impl OptimisticLockError {
+	pub fn new(entity_name: &'static str, key: impl Debug) -> Self {
+		Self { entity: entity_name, key: format!("{:?}", key) }
+	}
+
+	pub fn entity(&self) -> &'static str {
+		self.entity
+	}
+
+	/// the `Debug` rendering of the key
+	pub fn key(&self) -> &str {
+		&self.key
+	}
+}
+impl Display for OptimisticLockError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "Lost update on {} {{id={}}}", self.entity, self.key)
+	}
+}
+impl std::error::Error for OptimisticLockError {}
+
+
+/// A row of the entity already has the values of a unique key, named by its fields when the index is declared on the entity.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DuplicateError {
+	entity: &'static str,
+	fields: Vec<&'static str>,
+	index: Option<String>,
+}
+
+impl DuplicateError {
+	pub fn new(entity_name: &'static str, fields: Vec<&'static str>, index: Option<String>) -> Self {
+		Self { entity: entity_name, fields, index }
+	}
+
+	pub fn entity(&self) -> &'static str {
+		self.entity
+	}
 
-impl<Key: Debug> EntityNotFoundError<Key> {
-	pub fn new(entity_name: &'static str, key: Key) -> Self {
-		Self(entity_name, key)
+	/// the fields of the violated unique key, empty if the index is unknown to the entity
+	pub fn fields(&self) -> &[&'static str] {
+		&self.fields
+	}
+
+	/// the name of the violated index, as reported by the server
+	pub fn index(&self) -> Option<&str> {
+		self.index.as_deref()
 	}
 }
-impl<Key: Debug> Display for EntityNotFoundError<Key> {
+impl Display for DuplicateError {
 	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-		write!(f, "Not found {} {{id={:?}}})", self.0, self.1)
+		if self.fields.is_empty() {
+			write!(f, "Duplicate {} {{index={}}}", self.entity, self.index.as_deref().unwrap_or("?"))
+		} else {
+			write!(f, "Duplicate {} {{{}}}", self.entity, self.fields.join(", "))
+		}
 	}
 }
-impl<Key: Debug> std::error::Error for EntityNotFoundError<Key> {}
+impl std::error::Error for DuplicateError {}
 
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -31,6 +116,124 @@ impl Display for UnexpectedAffectedRowsError {
 impl std::error::Error for UnexpectedAffectedRowsError {}
 
 
+/// A nested transaction was requested on a plain connection, where there is no transaction to make a savepoint in.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct NoTransactionError;
+impl Display for NoTransactionError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "NoTransactionError(a savepoint needs a transaction)")
+	}
+}
+impl std::error::Error for NoTransactionError {}
+
+
+/// An insert generated no AUTO_INCREMENT value, because the table has none or no row was inserted.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct NoInsertIdError;
+impl Display for NoInsertIdError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "NoInsertIdError(no AUTO_INCREMENT value was generated)")
+	}
+}
+impl std::error::Error for NoInsertIdError {}
+
+
+/// `Table::field_value()` of the entity does not know the value of a field that a query needs, like a key field of a keyset scan.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct MissingFieldValueError {
+	entity: &'static str,
+	field: &'static str,
+}
+impl MissingFieldValueError {
+	pub fn new(entity_name: &'static str, field: &'static str) -> Self {
+		Self { entity: entity_name, field }
+	}
+
+	pub fn entity(&self) -> &'static str {
+		self.entity
+	}
+
+	pub fn field(&self) -> &'static str {
+		self.field
+	}
+}
+impl Display for MissingFieldValueError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "MissingFieldValueError(no value of {}.{})", self.entity, self.field)
+	}
+}
+impl std::error::Error for MissingFieldValueError {}
+
+
+/// A query on a tenant scoped entity is not restricted to a tenant, or would cross tenants.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TenantScopeError {
+	/// the entity is reached without a tenant scope, or its filter or inserted values have no tenant
+	Missing(&'static str),
+	/// the updates of the entity assign its tenant
+	Reassigned(&'static str),
+	/// the entity or the inserted values belong to another tenant than the scope
+	Mismatch(&'static str),
+}
+impl Display for TenantScopeError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			TenantScopeError::Missing(entity) => write!(f, "TenantScopeError(no tenant for {})", entity),
+			TenantScopeError::Reassigned(entity) => write!(f, "TenantScopeError(the tenant of {} is updated)", entity),
+			TenantScopeError::Mismatch(entity) => write!(f, "TenantScopeError({} of another tenant)", entity),
+		}
+	}
+}
+impl std::error::Error for TenantScopeError {}
+
+
+/// No connection of the pool became available within the checkout timeout.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct PoolTimeoutError(std::time::Duration);
+impl PoolTimeoutError {
+	pub fn new(timeout: std::time::Duration) -> Self {
+		Self(timeout)
+	}
+
+	pub fn timeout(&self) -> std::time::Duration {
+		self.0
+	}
+}
+impl Display for PoolTimeoutError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "PoolTimeoutError(no connection within {:?})", self.0)
+	}
+}
+impl std::error::Error for PoolTimeoutError {}
+
+
+/// A transaction still failed with a transient error after the last attempt allowed by the retry policy.
+#[derive(Debug)]
+pub struct RetriesExhaustedError {
+	attempts: u32,
+	source: Box<dyn std::error::Error + Send + Sync>,
+}
+impl RetriesExhaustedError {
+	pub fn new(attempts: u32, source: Box<dyn std::error::Error + Send + Sync>) -> Self {
+		Self { attempts, source }
+	}
+
+	pub fn attempts(&self) -> u32 {
+		self.attempts
+	}
+}
+impl Display for RetriesExhaustedError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "RetriesExhaustedError(attempts:{}, last:{})", self.attempts, self.source)
+	}
+}
+impl std::error::Error for RetriesExhaustedError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		Some(&*self.source)
+	}
+}
+
+
 #[derive(Debug)]
 pub struct FromStrError {
 	pub message: String,
@@ -50,4 +253,433 @@ impl Display for FromStrError {
 		write!(f, "{}", self.message)
 	}
 }
-impl std::error::Error for FromStrError {} 
+impl std::error::Error for FromStrError {}
+
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// The errors of the crate and of both drivers, classified so that callers can tell them apart.
+#[derive(Debug)]
+pub enum RepoError {
+	/// the entity and the key are unknown when the driver reports a missing row
+	NotFound { entity: Option<&'static str>, key: Option<String> },
+	/// the index is parsed from the message of the server, if possible
+	UniqueViolation { index: Option<String>, message: String },
+	/// a unique violation of an entity operation, translated with the unique keys of the entity
+	Duplicate(DuplicateError),
+	ForeignKeyViolation { message: String },
+	Deadlock { message: String },
+	/// a lock wait timeout, a statement interrupted by its maximum execution time, or a pool timeout
+	Timeout { message: String },
+	Connection(BoxError),
+	Decode(BoxError),
+	AffectedRows { expected: u64, actual: u64 },
+	OptimisticLock { entity: &'static str, key: String },
+	TenantScope(TenantScopeError),
+	/// a savepoint was requested outside of a transaction
+	NoTransaction,
+	/// an insert expected to generate an AUTO_INCREMENT value generated none
+	NoInsertId,
+	/// the value of a field the query needs is unknown to `Table::field_value()`
+	MissingFieldValue { entity: &'static str, field: &'static str },
+	/// a transaction still failed after the last attempt, with the classified error of the last attempt
+	RetriesExhausted { attempts: u32, last: Box<RepoError> },
+	Other(BoxError),
+}
+
+/// `ER_DUP_ENTRY`
+pub const ER_DUP_ENTRY: u16 = 1062;
+/// `ER_DUP_ENTRY_WITH_KEY_NAME`
+pub const ER_DUP_ENTRY_WITH_KEY_NAME: u16 = 1586;
+
+/// returns whether the server error `code` reports a duplicate entry of a unique key
+pub fn is_duplicate_entry_code(code: u16) -> bool {
+	code == ER_DUP_ENTRY || code == ER_DUP_ENTRY_WITH_KEY_NAME
+}
+
+impl RepoError {
+	/// classifies an error of the server by its code
+	pub fn from_server_error(code: u16, message: String, source: BoxError) -> Self {
+		match code {
+			ER_DUP_ENTRY | ER_DUP_ENTRY_WITH_KEY_NAME => {
+				RepoError::UniqueViolation { index: duplicate_key_index(&message), message }
+			},
+			// ER_ROW_IS_REFERENCED(_2), ER_NO_REFERENCED_ROW(_2)
+			1216 | 1217 | 1451 | 1452 => RepoError::ForeignKeyViolation { message },
+			ER_LOCK_DEADLOCK => RepoError::Deadlock { message },
+			ER_LOCK_WAIT_TIMEOUT | ER_QUERY_TIMEOUT => RepoError::Timeout { message },
+			_ => RepoError::Other(source),
+		}
+	}
+
+	/// classifies an error of either driver, or of the crate
+	pub fn from_boxed_error(error: BoxError) -> Self {
+		#[cfg(feature = "mysql_async_helper")]
+		let error = match error.downcast::<mysql_async::Error>() {
+			Ok(e) => return RepoError::from(*e),
+			Err(e) => e,
+		};
+		#[cfg(feature = "sqlx_mysql_helper")]
+		let error = match error.downcast::<sqlx::Error>() {
+			Ok(e) => return RepoError::from(*e),
+			Err(e) => e,
+		};
+		RepoError::from_boxed(error, RepoError::Other)
+	}
+
+	/// recovers the errors of the crate boxed in a driver error, and passes the others to `fallback`
+	fn from_boxed(error: BoxError, fallback: fn(BoxError) -> Self) -> Self {
+		macro_rules! downcast {
+			($error:ident, $($ty:ty => $classify:expr),+ $(,)?) => {
+				$(
+					let $error = match $error.downcast::<$ty>() {
+						Ok(e) => return $classify(*e),
+						Err(e) => e,
+					};
+				)+
+			};
+		}
+
+		downcast!(error,
+			RepoError => |e| e,
+			DuplicateError => RepoError::from,
+			PoolTimeoutError => |e: PoolTimeoutError| RepoError::Timeout { message: e.to_string() },
+			UnexpectedAffectedRowsError => RepoError::from,
+			TenantScopeError => RepoError::from,
+			NoTransactionError => RepoError::from,
+			NoInsertIdError => RepoError::from,
+			MissingFieldValueError => RepoError::from,
+			RetriesExhaustedError => RepoError::from,
+			FromStrError => RepoError::from,
+			EntityNotFoundError => RepoError::from,
+			OptimisticLockError => RepoError::from,
+		);
+		fallback(error)
+	}
+
+	pub fn is_not_found(&self) -> bool {
+		matches!(self, RepoError::NotFound { .. })
+	}
+
+	pub fn is_unique_violation(&self) -> bool {
+		matches!(self, RepoError::UniqueViolation { .. } | RepoError::Duplicate(_))
+	}
+
+	/// returns whether the transaction may succeed if run again.
+	/// The retry of a transaction relies on `is_transient_error()` of each backend, which agrees with it without taking the error.
+	pub fn is_transient(&self) -> bool {
+		matches!(self, RepoError::Deadlock { .. } | RepoError::Timeout { .. })
+	}
+}
+
+/// returns whether an error of the crate boxed in a driver error is transient, like its classification by `RepoError`
+#[cfg(any(feature = "mysql_async_helper", feature = "sqlx_mysql_helper"))]
+pub(crate) fn is_transient_boxed_error(error: &(dyn Error + Send + Sync + 'static)) -> bool {
+	if let Some(e) = error.downcast_ref::<RepoError>() {
+		return e.is_transient();
+	}
+	error.is::<PoolTimeoutError>()
+}
+
+/// returns `index` from "Duplicate entry '...' for key 'table.index'", or from "... for key 'index'" before MySQL 8.0.19
+pub(crate) fn duplicate_key_index(message: &str) -> Option<String> {
+	let (_, key) = message.rsplit_once(" for key '")?;
+	let key = key.strip_suffix('\'').unwrap_or(key);
+	let index = key.rsplit('.').next().unwrap_or(key);
+	Some(index.to_string())
+}
+
+impl Display for RepoError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			RepoError::NotFound { entity, key } => {
+				write!(f, "Not found {} {{id={}}}", entity.unwrap_or("row"), key.as_deref().unwrap_or("?"))
+			},
+			RepoError::UniqueViolation { message, .. } => write!(f, "Unique violation: {}", message),
+			RepoError::Duplicate(e) => write!(f, "{}", e),
+			RepoError::ForeignKeyViolation { message } => write!(f, "Foreign key violation: {}", message),
+			RepoError::Deadlock { message } => write!(f, "Deadlock: {}", message),
+			RepoError::Timeout { message } => write!(f, "Timeout: {}", message),
+			RepoError::Connection(e) => write!(f, "Connection error: {}", e),
+			RepoError::Decode(e) => write!(f, "Decode error: {}", e),
+			RepoError::AffectedRows { expected, actual } => {
+				write!(f, "UnexpectedAffectedRowsError(expected:{}, actual:{})", expected, actual)
+			},
+			RepoError::OptimisticLock { entity, key } => write!(f, "Lost update on {} {{id={}}}", entity, key),
+			RepoError::TenantScope(e) => write!(f, "{}", e),
+			RepoError::NoTransaction => write!(f, "{}", NoTransactionError),
+			RepoError::NoInsertId => write!(f, "{}", NoInsertIdError),
+			RepoError::MissingFieldValue { entity, field } => write!(f, "{}", MissingFieldValueError::new(entity, field)),
+			RepoError::RetriesExhausted { attempts, last } => {
+				write!(f, "RetriesExhaustedError(attempts:{}, last:{})", attempts, last)
+			},
+			RepoError::Other(e) => write!(f, "{}", e),
+		}
+	}
+}
+
+impl Error for RepoError {
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+		match self {
+			RepoError::Connection(e) | RepoError::Decode(e) | RepoError::Other(e) => Some(&**e),
+			RepoError::RetriesExhausted { last, .. } => Some(&**last),
+			_ => None,
+		}
+	}
+}
+
+impl From<EntityNotFoundError> for RepoError {
+	fn from(e: EntityNotFoundError) -> Self {
+		RepoError::NotFound { entity: Some(e.entity), key: Some(e.key) }
+	}
+}
+
+impl From<OptimisticLockError> for RepoError {
+	fn from(e: OptimisticLockError) -> Self {
+		RepoError::OptimisticLock { entity: e.entity, key: e.key }
+	}
+}
+
+impl From<DuplicateError> for RepoError {
+	fn from(e: DuplicateError) -> Self {
+		RepoError::Duplicate(e)
+	}
+}
+
+impl From<UnexpectedAffectedRowsError> for RepoError {
+	fn from(e: UnexpectedAffectedRowsError) -> Self {
+		RepoError::AffectedRows { expected: e.0, actual: e.1 }
+	}
+}
+
+impl From<TenantScopeError> for RepoError {
+	fn from(e: TenantScopeError) -> Self {
+		RepoError::TenantScope(e)
+	}
+}
+
+impl From<NoTransactionError> for RepoError {
+	fn from(_: NoTransactionError) -> Self {
+		RepoError::NoTransaction
+	}
+}
+
+impl From<NoInsertIdError> for RepoError {
+	fn from(_: NoInsertIdError) -> Self {
+		RepoError::NoInsertId
+	}
+}
+
+impl From<MissingFieldValueError> for RepoError {
+	fn from(e: MissingFieldValueError) -> Self {
+		RepoError::MissingFieldValue { entity: e.entity, field: e.field }
+	}
+}
+
+impl From<RetriesExhaustedError> for RepoError {
+	fn from(e: RetriesExhaustedError) -> Self {
+		RepoError::RetriesExhausted { attempts: e.attempts, last: Box::new(RepoError::from_boxed_error(e.source)) }
+	}
+}
+
+impl From<FromStrError> for RepoError {
+	fn from(e: FromStrError) -> Self {
+		RepoError::Decode(Box::new(e))
+	}
+}
+
+#[cfg(feature = "mysql_async_helper")]
+impl From<mysql_async::Error> for RepoError {
+	fn from(e: mysql_async::Error) -> Self {
+		use mysql_async::{DriverError, Error};
+
+		match e {
+			Error::Server(server) => {
+				let (code, message) = (server.code, server.message.clone());
+				RepoError::from_server_error(code, message, Box::new(server))
+			},
+			Error::Io(io) => RepoError::Connection(Box::new(io)),
+			Error::Driver(DriverError::ConnectionClosed | DriverError::PoolDisconnected) => {
+				RepoError::Connection(Box::new(e))
+			},
+			Error::Driver(DriverError::FromValue { .. } | DriverError::FromRow { .. }) => RepoError::Decode(Box::new(e)),
+			Error::Other(boxed) => RepoError::from_boxed(boxed, RepoError::Other),
+			e => RepoError::Other(Box::new(e)),
+		}
+	}
+}
+
+#[cfg(feature = "sqlx_mysql_helper")]
+impl From<sqlx::Error> for RepoError {
+	fn from(e: sqlx::Error) -> Self {
+		use sqlx::mysql::MySqlDatabaseError;
+
+		match e {
+			sqlx::Error::Database(db) => match db.try_downcast_ref::<MySqlDatabaseError>() {
+				Some(mysql) => {
+					let (code, message) = (mysql.number(), mysql.message().to_string());
+					RepoError::from_server_error(code, message, Box::new(sqlx::Error::Database(db)))
+				},
+				None => RepoError::Other(Box::new(sqlx::Error::Database(db))),
+			},
+			sqlx::Error::RowNotFound => RepoError::NotFound { entity: None, key: None },
+			sqlx::Error::PoolTimedOut => RepoError::Timeout { message: e.to_string() },
+			sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::PoolClosed | sqlx::Error::WorkerCrashed => {
+				RepoError::Connection(Box::new(e))
+			},
+			sqlx::Error::Decode(boxed) => RepoError::from_boxed(boxed, RepoError::Decode),
+			sqlx::Error::ColumnDecode { .. } | sqlx::Error::ColumnNotFound(_) | sqlx::Error::TypeNotFound { .. } => {
+				RepoError::Decode(Box::new(e))
+			},
+			e => RepoError::Other(Box::new(e)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use crate::is_transient_error_code;
+	use super::*;
+
+	/// classifies `error` as boxed by the crate in the errors of each enabled driver, and directly
+	fn classify<E: Error + Send + Sync + Clone + 'static>(error: E) -> Vec<RepoError> {
+		#[allow(unused_mut)]
+		let mut classified = vec![RepoError::from_boxed_error(Box::new(error.clone()))];
+		#[cfg(feature = "mysql_async_helper")]
+		classified.push(RepoError::from(mysql_async::Error::Other(Box::new(error.clone()))));
+		#[cfg(feature = "sqlx_mysql_helper")]
+		classified.push(RepoError::from(sqlx::Error::Decode(Box::new(error.clone()))));
+		classified
+	}
+
+	#[test]
+	fn entity_errors_keep_their_entity_and_key() {
+		for e in classify(EntityNotFoundError::new("Order", 42u64)) {
+			assert!(e.is_not_found());
+			assert!(matches!(e, RepoError::NotFound { entity: Some("Order"), key: Some(ref key) } if key == "42"));
+		}
+		for e in classify(OptimisticLockError::new("Account", (3i64, "eu".to_string()))) {
+			assert!(matches!(e, RepoError::OptimisticLock { entity: "Account", ref key } if key == "(3, \"eu\")"));
+		}
+		for e in classify(EntityNotFoundError::new("Product", ('L', 2u8))) {
+			assert!(matches!(e, RepoError::NotFound { entity: Some("Product"), key: Some(ref key) } if key == "('L', 2)"));
+		}
+		for e in classify(DuplicateError::new("User", vec!["email"], Some("email".to_string()))) {
+			assert!(e.is_unique_violation());
+			assert!(matches!(e, RepoError::Duplicate(ref d) if d.fields() == ["email"]));
+		}
+	}
+
+	#[test]
+	fn usage_errors_are_classified() {
+		for e in classify(UnexpectedAffectedRowsError::new(1, 2)) {
+			assert!(matches!(e, RepoError::AffectedRows { expected: 1, actual: 2 }));
+		}
+		for e in classify(TenantScopeError::Missing("Invoice")) {
+			assert!(matches!(e, RepoError::TenantScope(TenantScopeError::Missing("Invoice"))));
+		}
+		for e in classify(NoTransactionError) {
+			assert!(matches!(e, RepoError::NoTransaction));
+		}
+		for e in classify(NoInsertIdError) {
+			assert!(matches!(e, RepoError::NoInsertId));
+		}
+		for e in classify(MissingFieldValueError::new("Order", "id")) {
+			assert!(matches!(e, RepoError::MissingFieldValue { entity: "Order", field: "id" }));
+		}
+	}
+
+	#[test]
+	fn pool_timeouts_are_transient() {
+		for e in classify(PoolTimeoutError::new(Duration::from_secs(2))) {
+			assert!(matches!(e, RepoError::Timeout { .. }));
+			assert!(e.is_transient());
+		}
+	}
+
+	#[test]
+	fn decode_errors_are_classified() {
+		let e = RepoError::from_boxed_error(Box::new(FromStrError::from("unknown status 'x'")));
+		assert!(matches!(e, RepoError::Decode(ref source) if source.to_string() == "unknown status 'x'"));
+	}
+
+	#[test]
+	fn exhausted_retries_keep_the_last_error() {
+		let deadlock = RepoError::Deadlock { message: "Deadlock found".to_string() };
+		let e = RepoError::from_boxed_error(Box::new(RetriesExhaustedError::new(3, Box::new(deadlock))));
+		assert!(!e.is_transient());
+		match e {
+			RepoError::RetriesExhausted { attempts: 3, last } => assert!(matches!(*last, RepoError::Deadlock { .. })),
+			e => panic!("unexpected {e:?}"),
+		}
+	}
+
+	#[cfg(feature = "mysql_async_helper")]
+	#[test]
+	fn exhausted_retries_classify_the_driver_error() {
+		let error = mysql_async::Error::Other(Box::new(RetriesExhaustedError::new(2, Box::new(mysql_async::Error::Other(Box::new(NoTransactionError))))));
+		match RepoError::from(error) {
+			RepoError::RetriesExhausted { attempts: 2, last } => assert!(matches!(*last, RepoError::NoTransaction)),
+			e => panic!("unexpected {e:?}"),
+		}
+	}
+
+	#[test]
+	fn server_errors_are_classified_by_code() {
+		let message = "Duplicate entry 'a@b.c' for key 'users.uk_email'".to_string();
+		let e = RepoError::from_server_error(ER_DUP_ENTRY, message.clone(), FromStrError::from(message).into());
+		assert!(matches!(e, RepoError::UniqueViolation { index: Some(ref index), .. } if index == "uk_email"));
+
+		let e = RepoError::from_server_error(1213, "Deadlock found".to_string(), FromStrError::from("").into());
+		assert!(e.is_transient());
+		let e = RepoError::from_server_error(1452, "Cannot add or update a child row".to_string(), FromStrError::from("").into());
+		assert!(matches!(e, RepoError::ForeignKeyViolation { .. }));
+	}
+
+	#[test]
+	fn transient_codes_are_classified_as_transient() {
+		for code in [ER_LOCK_DEADLOCK, ER_LOCK_WAIT_TIMEOUT, ER_QUERY_TIMEOUT, ER_DUP_ENTRY, 1452, 1064] {
+			let e = RepoError::from_server_error(code, String::new(), FromStrError::from("").into());
+			assert_eq!(e.is_transient(), is_transient_error_code(code), "code {code}");
+		}
+	}
+
+	#[cfg(feature = "mysql_async_helper")]
+	#[test]
+	fn mysql_async_retries_follow_the_classification() {
+		let server = |code| mysql_async::Error::Server(mysql_async::ServerError { code, message: String::new(), state: String::new() });
+		let errors = || vec![
+			server(ER_LOCK_DEADLOCK),
+			server(ER_QUERY_TIMEOUT),
+			server(ER_DUP_ENTRY),
+			mysql_async::Error::Other(Box::new(PoolTimeoutError::new(Duration::from_secs(1)))),
+			mysql_async::Error::Other(Box::new(RepoError::Deadlock { message: String::new() })),
+			mysql_async::Error::Other(Box::new(NoTransactionError)),
+		];
+		for (error, classified) in errors().iter().zip(errors()) {
+			assert_eq!(crate::mysql::is_transient_error(error), RepoError::from(classified).is_transient(), "{error:?}");
+		}
+	}
+
+	#[cfg(feature = "sqlx_mysql_helper")]
+	#[test]
+	fn sqlx_retries_follow_the_classification() {
+		let errors = || vec![
+			sqlx::Error::PoolTimedOut,
+			sqlx::Error::RowNotFound,
+			sqlx::Error::Decode(Box::new(RepoError::Timeout { message: String::new() })),
+			sqlx::Error::Decode(Box::new(NoTransactionError)),
+		];
+		for (error, classified) in errors().iter().zip(errors()) {
+			assert_eq!(crate::sqlx::is_transient_error(error), RepoError::from(classified).is_transient(), "{error:?}");
+		}
+	}
+
+	#[test]
+	fn unknown_errors_are_other() {
+		let e = RepoError::from_boxed_error(Box::new(std::io::Error::other("boom")));
+		assert!(matches!(e, RepoError::Other(_)));
+	}
+}
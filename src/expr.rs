@@ -0,0 +1,264 @@
+use std::borrow::Cow;
+use std::ops::{Add, Div, Mul, Sub};
+
+use crate::FromStrError;
+
+/// A column name, which may be qualified with a table name (`orders.created_at`).
+/// Only ASCII letters, digits and `_` are accepted, so that a column can never carry raw SQL.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column(Cow<'static, str>);
+
+impl Column {
+	/// fails if `name` is not a valid column name
+	pub fn new(name: &str) -> Result<Self, FromStrError> {
+		if !Self::is_valid(name) {
+			return Err(format!("Invalid column name {:?}", name).into());
+		}
+		Ok(Column(Cow::Owned(name.to_string())))
+	}
+
+	/// for a column name written in the code, which is checked at compile time in a const context,
+	/// e.g. `const SHIPPED_AT: Column = Column::literal("orders.shipped_at");`, and panics if invalid otherwise
+	pub const fn literal(name: &'static str) -> Self {
+		assert!(Self::is_valid(name), "invalid column name");
+		Column(Cow::Borrowed(name))
+	}
+
+	/// for the names of the fields of an entity, which are identifiers
+	pub(crate) fn trusted(name: &str) -> Self {
+		debug_assert!(Self::is_valid(name));
+		Column(Cow::Owned(name.to_string()))
+	}
+
+	pub fn name(&self) -> &str {
+		&self.0
+	}
+
+	/// dot separated parts, each made of ASCII letters, digits and `_`
+	const fn is_valid(name: &str) -> bool {
+		let bytes = name.as_bytes();
+		let mut i = 0;
+		let mut part_len = 0;
+		while i < bytes.len() {
+			match bytes[i] {
+				b'.' if part_len > 0 => part_len = 0,
+				c if c.is_ascii_alphanumeric() || c == b'_' => part_len += 1,
+				_ => return false,
+			}
+			i += 1;
+		}
+		part_len > 0
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqlFunction {
+	Date,
+	Year,
+	Month,
+	Day,
+	Lower,
+	Upper,
+	Length,
+	Abs,
+	Coalesce,
+	Now,
+	CurrentDate,
+}
+
+impl SqlFunction {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			SqlFunction::Date => "DATE",
+			SqlFunction::Year => "YEAR",
+			SqlFunction::Month => "MONTH",
+			SqlFunction::Day => "DAY",
+			SqlFunction::Lower => "LOWER",
+			SqlFunction::Upper => "UPPER",
+			SqlFunction::Length => "LENGTH",
+			SqlFunction::Abs => "ABS",
+			SqlFunction::Coalesce => "COALESCE",
+			SqlFunction::Now => "NOW",
+			SqlFunction::CurrentDate => "CURRENT_DATE",
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithmeticOp {
+	Add,
+	Subtract,
+	Multiply,
+	Divide,
+}
+
+impl ArithmeticOp {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			ArithmeticOp::Add => "+",
+			ArithmeticOp::Subtract => "-",
+			ArithmeticOp::Multiply => "*",
+			ArithmeticOp::Divide => "/",
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+	Equal,
+	Not,
+	LessorThan,
+	EqualOrLessorThan,
+	GreaterThan,
+	EqualOrGreaterThan,
+}
+
+impl Comparison {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Comparison::Equal => "=",
+			Comparison::Not => "<>",
+			Comparison::LessorThan => "<",
+			Comparison::EqualOrLessorThan => "<=",
+			Comparison::GreaterThan => ">",
+			Comparison::EqualOrGreaterThan => ">=",
+		}
+	}
+
+	/// renders `{lhs}{op}{rhs}`, asking `holder` for the text of every value in the order of `values()`
+	pub fn render<T>(&self, lhs: &Expr<T>, rhs: &Expr<T>, holder: &mut dyn FnMut(&T) -> String) -> String {
+		format!("{}{}{}", lhs.render(holder), self.as_str(), rhs.render(holder))
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr<T> {
+	Column(Column),
+	Value(T),
+	Function(SqlFunction, Vec<Expr<T>>),
+	Arithmetic(Box<Expr<T>>, ArithmeticOp, Box<Expr<T>>),
+}
+
+impl<T> Expr<T> {
+	/// fails if `name` is not a valid column name
+	pub fn column(name: &str) -> Result<Self, FromStrError> {
+		Column::new(name).map(Expr::Column)
+	}
+
+	pub fn value(value: T) -> Self {
+		Expr::Value(value)
+	}
+
+	pub fn function(function: SqlFunction, args: Vec<Expr<T>>) -> Self {
+		Expr::Function(function, args)
+	}
+
+	/// renders the expression, asking `holder` for the text of every value in the order of `values()`
+	pub fn render(&self, holder: &mut dyn FnMut(&T) -> String) -> String {
+		match self {
+			Expr::Column(column) => column.name().to_string(),
+			Expr::Value(value) => holder(value),
+			Expr::Function(function, args) => {
+				let args = args.iter()
+					.map(|arg| arg.render(holder))
+					.collect::<Vec<String>>()
+					.join(", ");
+				format!("{}({})", function.as_str(), args)
+			},
+			Expr::Arithmetic(lhs, op, rhs) => {
+				format!("({} {} {})", lhs.render(holder), op.as_str(), rhs.render(holder))
+			},
+		}
+	}
+
+	pub fn values(&self) -> Vec<&T> {
+		match self {
+			Expr::Column(_) => vec![],
+			Expr::Value(value) => vec![value],
+			Expr::Function(_, args) => args.iter().flat_map(|arg| arg.values()).collect(),
+			Expr::Arithmetic(lhs, _, rhs) => {
+				let mut values = lhs.values();
+				values.extend(rhs.values());
+				values
+			},
+		}
+	}
+}
+
+impl<T: Clone> Expr<T> {
+	pub(crate) fn map_dyn<R>(&self, f: &dyn Fn(T) -> R) -> Expr<R> {
+		match self {
+			Expr::Column(column) => Expr::Column(column.clone()),
+			Expr::Value(value) => Expr::Value(f(value.clone())),
+			Expr::Function(function, args) => Expr::Function(*function, args.iter().map(|arg| arg.map_dyn(f)).collect()),
+			Expr::Arithmetic(lhs, op, rhs) => Expr::Arithmetic(Box::new(lhs.map_dyn(f)), *op, Box::new(rhs.map_dyn(f))),
+		}
+	}
+}
+
+impl<T> From<Column> for Expr<T> {
+	fn from(column: Column) -> Self {
+		Expr::Column(column)
+	}
+}
+
+impl<T> Add for Expr<T> {
+	type Output = Expr<T>;
+
+	fn add(self, rhs: Self) -> Self::Output {
+		Expr::Arithmetic(Box::new(self), ArithmeticOp::Add, Box::new(rhs))
+	}
+}
+
+impl<T> Sub for Expr<T> {
+	type Output = Expr<T>;
+
+	fn sub(self, rhs: Self) -> Self::Output {
+		Expr::Arithmetic(Box::new(self), ArithmeticOp::Subtract, Box::new(rhs))
+	}
+}
+
+impl<T> Mul for Expr<T> {
+	type Output = Expr<T>;
+
+	fn mul(self, rhs: Self) -> Self::Output {
+		Expr::Arithmetic(Box::new(self), ArithmeticOp::Multiply, Box::new(rhs))
+	}
+}
+
+impl<T> Div for Expr<T> {
+	type Output = Expr<T>;
+
+	fn div(self, rhs: Self) -> Self::Output {
+		Expr::Arithmetic(Box::new(self), ArithmeticOp::Divide, Box::new(rhs))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn expressions_render_columns_functions_and_arithmetic() {
+		let lhs = Expr::function(SqlFunction::Date, vec![Expr::column("orders.shipped_at").unwrap()]);
+		let rhs = Expr::from(Column::literal("ordered_at")) + Expr::value(3) * Expr::value(2);
+		let sql = Comparison::GreaterThan.render(&lhs, &rhs, &mut |v: &i32| format!("<{v}>"));
+		assert_eq!(sql, "DATE(orders.shipped_at)>(ordered_at + (<3> * <2>))");
+		assert_eq!(rhs.values(), [&3, &2]);
+	}
+
+	#[test]
+	fn qualified_columns_are_accepted() {
+		assert_eq!(Column::new("orders.created_at").unwrap().name(), "orders.created_at");
+		const CREATED_AT: Column = Column::literal("orders.created_at");
+		assert_eq!(CREATED_AT, Column::new("orders.created_at").unwrap());
+	}
+
+	#[test]
+	fn columns_never_carry_raw_sql() {
+		for name in ["id; DROP TABLE orders", "", "orders.", ".id", "orders..id", "`id`"] {
+			assert!(Column::new(name).is_err(), "{name:?} is accepted");
+			assert!(Expr::<i32>::column(name).is_err(), "{name:?} is accepted");
+		}
+	}
+}
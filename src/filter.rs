@@ -1,4 +1,4 @@
-use super::RepoValue;
+use super::{RepoValue, SqlFilter, Table, Expr, Comparison, Column, JsonPath, FromStrError};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Filter<T> {
@@ -11,11 +11,31 @@ pub enum Filter<T> {
 	In(Vec<T>),
 	NotIn(Vec<T>),
 	Between(T, T),
+	IsNull,
+	IsNotNull,
+	InSubquery(Subquery<T>),
+	NotInSubquery(Subquery<T>),
+	Exists(Subquery<T>),
+	NotExists(Subquery<T>),
+	/// compares two expressions; the name of the filter only labels its parameters
+	Compare(Expr<T>, Comparison, Expr<T>),
+	Raw(RawSql<T>),
+	Match(FullTextMatch<T>),
+	/// `JSON_EXTRACT(field, path) {op} value`
+	JsonExtract(JsonPath, Comparison, T),
+	/// `JSON_CONTAINS(field, value, path)`, where value is a JSON document
+	JsonContains(T, JsonPath),
+	/// `value MEMBER OF(JSON_EXTRACT(field, path))`
+	JsonMemberOf(T, JsonPath),
 }
 
 impl<T: Clone> Filter<T> {
 	pub fn map<F, R:Clone>(&self, f: F) -> Filter<R>
 	where F: Fn(T) -> R {
+		self.map_dyn(&f)
+	}
+
+	fn map_dyn<R: Clone>(&self, f: &dyn Fn(T) -> R) -> Filter<R> {
 		match self {
 			Self::Equal(value) => Filter::Equal(f(value.clone())),
 			Self::Not(value) => Filter::Not(f(value.clone())),
@@ -26,10 +46,269 @@ impl<T: Clone> Filter<T> {
 			Self::In(v) => Filter::In(v.iter().map(|v| f(v.clone())).collect()),
 			Self::NotIn(v) => Filter::NotIn(v.iter().map(|v| f(v.clone())).collect()),
 			Self::Between(v1, v2) => Filter::Between(f(v1.clone()), f(v2.clone())),
+			Self::IsNull => Filter::IsNull,
+			Self::IsNotNull => Filter::IsNotNull,
+			Self::InSubquery(s) => Filter::InSubquery(s.map_dyn(f)),
+			Self::NotInSubquery(s) => Filter::NotInSubquery(s.map_dyn(f)),
+			Self::Exists(s) => Filter::Exists(s.map_dyn(f)),
+			Self::NotExists(s) => Filter::NotExists(s.map_dyn(f)),
+			Self::Compare(lhs, op, rhs) => Filter::Compare(lhs.map_dyn(f), *op, rhs.map_dyn(f)),
+			Self::Raw(raw) => Filter::Raw(raw.map_dyn(f)),
+			Self::Match(m) => Filter::Match(m.map_dyn(f)),
+			Self::JsonExtract(path, op, value) => Filter::JsonExtract(path.clone(), *op, f(value.clone())),
+			Self::JsonContains(value, path) => Filter::JsonContains(f(value.clone()), path.clone()),
+			Self::JsonMemberOf(value, path) => Filter::JsonMemberOf(f(value.clone()), path.clone()),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchMode {
+	NaturalLanguage,
+	Boolean,
+	QueryExpansion,
+}
+
+impl MatchMode {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			MatchMode::NaturalLanguage => "IN NATURAL LANGUAGE MODE",
+			MatchMode::Boolean => "IN BOOLEAN MODE",
+			MatchMode::QueryExpansion => "WITH QUERY EXPANSION",
+		}
+	}
+}
+
+/// Which rows of a table with soft delete are matched, according to its deletion timestamp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SoftDeleteScope {
+	/// only the rows not deleted
+	#[default]
+	Active,
+	/// every row, deleted or not
+	All,
+	/// only the deleted rows
+	Deleted,
+}
+
+impl SoftDeleteScope {
+	pub fn filter<T>(&self) -> Option<Filter<T>> {
+		match self {
+			SoftDeleteScope::Active => Some(Filter::IsNull),
+			SoftDeleteScope::All => None,
+			SoftDeleteScope::Deleted => Some(Filter::IsNotNull),
+		}
+	}
+}
+
+/// `MATCH(columns) AGAINST (query mode)` over a FULLTEXT index, which must cover exactly the given columns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FullTextMatch<T> {
+	columns: Vec<Column>,
+	query: T,
+	mode: MatchMode,
+}
+
+impl<T> FullTextMatch<T> {
+	/// fails if there is no column or if a column name is invalid
+	pub fn new(columns: &[&str], query: T, mode: MatchMode) -> Result<Self, FromStrError> {
+		if columns.is_empty() {
+			return Err("MATCH requires at least one column".into());
+		}
+		let columns = columns.iter().map(|c| Column::new(c)).collect::<Result<_, _>>()?;
+		Ok(FullTextMatch { columns, query, mode })
+	}
+
+	pub fn query(&self) -> &T {
+		&self.query
+	}
+
+	pub fn mode(&self) -> MatchMode {
+		self.mode
+	}
+
+	pub fn render(&self, holder: &str) -> String {
+		let columns = self.columns.iter()
+			.map(|c| c.name())
+			.collect::<Vec<&str>>()
+			.join(", ");
+		format!("MATCH({columns}) AGAINST ({holder} {})", self.mode.as_str())
+	}
+}
+
+impl<T: Clone> FullTextMatch<T> {
+	fn map_dyn<R>(&self, f: &dyn Fn(T) -> R) -> FullTextMatch<R> {
+		FullTextMatch { columns: self.columns.clone(), query: f(self.query.clone()), mode: self.mode }
+	}
+}
+
+/// A hand-written SQL condition with `?` placeholders for its parameters.
+/// `?` inside quoted strings, quoted identifiers and comments (`-- `, `#` and `/* */`) is not treated as a placeholder.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawSql<T> {
+	sql: String,
+	params: Vec<T>,
+}
+
+impl<T> RawSql<T> {
+	/// fails if the fragment does not have one placeholder per parameter
+	pub fn new(sql: &str, params: Vec<T>) -> Result<Self, FromStrError> {
+		let placeholders = Self::split(sql).len() - 1;
+		if placeholders != params.len() {
+			return Err(format!("Invalid SQL fragment {:?}: {} placeholders but {} parameters", sql, placeholders, params.len()).into());
+		}
+		Ok(RawSql { sql: sql.to_string(), params })
+	}
+
+	/// for the fragments rendered by the crate itself, which have one placeholder per parameter by construction
+	pub(crate) fn trusted(sql: String, params: Vec<T>) -> Self {
+		debug_assert_eq!(Self::split(&sql).len() - 1, params.len());
+		RawSql { sql, params }
+	}
+
+	pub fn params(&self) -> &[T] {
+		&self.params
+	}
+
+	/// renders the fragment, replacing the i-th placeholder with `holder(i)`
+	pub fn render(&self, holder: &mut dyn FnMut(usize) -> String) -> String {
+		let mut sql = String::new();
+		for (i, part) in Self::split(&self.sql).into_iter().enumerate() {
+			if i > 0 {
+				sql.push_str(&holder(i - 1));
+			}
+			sql.push_str(part);
+		}
+		format!("({sql})")
+	}
+
+	/// splits the fragment at every placeholder
+	fn split(sql: &str) -> Vec<&str> {
+		let bytes = sql.as_bytes();
+		let mut parts = Vec::new();
+		let mut start = 0;
+		let mut i = 0;
+		while i < bytes.len() {
+			i = match bytes[i] {
+				quote @ (b'\'' | b'"' | b'`') => Self::skip_quoted(bytes, i + 1, quote),
+				b'#' => Self::skip_line(bytes, i),
+				b'-' if bytes.get(i + 1) == Some(&b'-') && bytes.get(i + 2).is_none_or(|c| c.is_ascii_whitespace()) => Self::skip_line(bytes, i),
+				b'/' if bytes.get(i + 1) == Some(&b'*') => {
+					sql[i + 2..].find("*/").map_or(bytes.len(), |end| i + 2 + end + 2)
+				},
+				b'?' => {
+					parts.push(&sql[start..i]);
+					start = i + 1;
+					i + 1
+				},
+				_ => i + 1,
+			};
+		}
+		parts.push(&sql[start..]);
+		parts
+	}
+
+	/// returns the position after the closing quote
+	fn skip_quoted(bytes: &[u8], mut i: usize, quote: u8) -> usize {
+		while i < bytes.len() {
+			match bytes[i] {
+				b'\\' => i += 2,
+				c if c == quote => return i + 1,
+				_ => i += 1,
+			}
+		}
+		bytes.len()
+	}
+
+	/// returns the position of the end of the line
+	fn skip_line(bytes: &[u8], i: usize) -> usize {
+		bytes[i..].iter().position(|c| *c == b'\n').map_or(bytes.len(), |end| i + end)
+	}
+}
+
+impl<T: Clone> RawSql<T> {
+	fn map_dyn<R>(&self, f: &dyn Fn(T) -> R) -> RawSql<R> {
+		RawSql {
+			sql: self.sql.clone(),
+			params: self.params.iter().map(|v| f(v.clone())).collect(),
 		}
 	}
 }
 
+/// `SELECT {column} FROM {table} WHERE ...`, used as the operand of `IN`, `NOT IN`, `EXISTS` and `NOT EXISTS`.
+/// Correlations are rendered as `{inner}={outer}` so that the subquery can refer to the outer query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subquery<T> {
+	table: &'static str,
+	column: &'static str,
+	filters: Vec<(String, Filter<T>)>,
+	correlations: Vec<(&'static str, &'static str)>,
+}
+
+impl<'a> Subquery<RepoValue<'a>> {
+	pub fn new(table: &'static str, column: &'static str, filter: SqlFilter<'a>) -> Self {
+		let filters = filter.into_iter()
+			.map(|NamedFilter(name, filter)| (name.to_string(), filter))
+			.collect();
+		Subquery { table, column, filters, correlations: Vec::new() }
+	}
+
+	pub fn of<E: Table>(column: &'static str, filter: SqlFilter<'a>) -> Self {
+		Self::new(E::TABLE_NAME, column, filter)
+	}
+}
+
+impl<T> Subquery<T> {
+	pub fn correlate(mut self, inner: &'static str, outer: &'static str) -> Self {
+		self.correlations.push((inner, outer));
+		self
+	}
+
+	pub fn table(&self) -> &'static str {
+		self.table
+	}
+
+	pub fn column(&self) -> &'static str {
+		self.column
+	}
+
+	pub fn filters(&self) -> impl Iterator<Item = (&str, &Filter<T>)> {
+		self.filters.iter().map(|(name, filter)| (name.as_str(), filter))
+	}
+
+	/// renders the subquery with the given conditions, which must be in the order of `filters()`
+	pub fn select_expression(&self, conditions: Vec<String>) -> String {
+		let conditions = conditions.into_iter()
+			.chain(self.correlations.iter().map(|(inner, outer)| format!("{inner}={outer}")))
+			.collect::<Vec<String>>();
+		if conditions.is_empty() {
+			format!("SELECT {} FROM {}", self.column, self.table)
+		} else {
+			format!("SELECT {} FROM {} WHERE {}", self.column, self.table, conditions.join(" AND "))
+		}
+	}
+}
+
+impl<T: Clone> Subquery<T> {
+	fn map_dyn<R: Clone>(&self, f: &dyn Fn(T) -> R) -> Subquery<R> {
+		Subquery {
+			table: self.table,
+			column: self.column,
+			filters: self.filters.iter().map(|(name, filter)| (name.clone(), filter.map_dyn(f))).collect(),
+			correlations: self.correlations.clone(),
+		}
+	}
+}
+
+impl Subquery<RepoValue<'_>> {
+	pub fn sql_expression(&self) -> String {
+		let conditions = self.filters()
+			.map(|(field, filter)| NamedFilter::expression(field, filter))
+			.collect();
+		self.select_expression(conditions)
+	}
+}
+
 #[derive(Clone)]
 pub struct NamedFilter<'a>(&'a str, Filter<RepoValue<'a>>);
 
@@ -47,8 +326,11 @@ impl<'a> NamedFilter<'a> {
 	}
 
 	pub fn sql_expression(&self) -> String {
-		let field = self.name();
-		match self.filter() {
+		Self::expression(self.name(), self.filter())
+	}
+
+	fn expression(field: &str, filter: &Filter<RepoValue<'_>>) -> String {
+		match filter {
 			Filter::Equal(value) => format!("{field}={value}"),
 			Filter::Not(value) => format!("{field}<>{value}"),
 			Filter::LessorThan(value) => format!("{field}<{value}"),
@@ -64,6 +346,18 @@ impl<'a> NamedFilter<'a> {
 				format!("{field} NOT IN ({expr})")
 			},
 			Filter::Between(v1, v2) => format!("{field} BETWEEN {v1} AND {v2}"),
+			Filter::IsNull => format!("{field} IS NULL"),
+			Filter::IsNotNull => format!("{field} IS NOT NULL"),
+			Filter::InSubquery(s) => format!("{field} IN ({})", s.sql_expression()),
+			Filter::NotInSubquery(s) => format!("{field} NOT IN ({})", s.sql_expression()),
+			Filter::Exists(s) => format!("EXISTS ({})", s.sql_expression()),
+			Filter::NotExists(s) => format!("NOT EXISTS ({})", s.sql_expression()),
+			Filter::Compare(lhs, op, rhs) => op.render(lhs, rhs, &mut |v| v.to_string()),
+			Filter::Raw(raw) => raw.render(&mut |i| raw.params()[i].to_string()),
+			Filter::Match(m) => m.render(&m.query().to_string()),
+			Filter::JsonExtract(path, op, value) => format!("{}{}{value}", path.extract_expression(field), op.as_str()),
+			Filter::JsonContains(value, path) => format!("JSON_CONTAINS({field}, {value}, {})", path.sql_literal()),
+			Filter::JsonMemberOf(value, path) => format!("{value} MEMBER OF({})", path.extract_expression(field)),
 		}
 	}
 
@@ -137,6 +431,43 @@ impl<'a> NamedFilterHolder<'a> {
 		self.1 = Some(Filter::Between(value1.into(), value2.into()));
 	}
 
+	pub fn is_null(&mut self) {
+		self.1 = Some(Filter::IsNull);
+	}
+
+	pub fn is_not_null(&mut self) {
+		self.1 = Some(Filter::IsNotNull);
+	}
+
+	pub fn in_subquery(&mut self, subquery: Subquery<RepoValue<'a>>) {
+		self.1 = Some(Filter::InSubquery(subquery));
+	}
+
+	pub fn not_in_subquery(&mut self, subquery: Subquery<RepoValue<'a>>) {
+		self.1 = Some(Filter::NotInSubquery(subquery));
+	}
+
+	/// compares the field with `rhs`, e.g. `updated_at > created_at`
+	pub fn compare(&mut self, op: Comparison, rhs: Expr<RepoValue<'a>>) {
+		self.1 = Some(Filter::Compare(Expr::Column(Column::trusted(self.0)), op, rhs));
+	}
+
+	pub fn matches<T: Into<RepoValue<'a>>>(&mut self, query: T, mode: MatchMode) {
+		self.1 = Some(Filter::Match(FullTextMatch { columns: vec![Column::trusted(self.0)], query: query.into(), mode }));
+	}
+
+	pub fn json_extract<T: Into<RepoValue<'a>>>(&mut self, path: JsonPath, op: Comparison, value: T) {
+		self.1 = Some(Filter::JsonExtract(path, op, value.into()));
+	}
+
+	pub fn json_contains<T: Into<RepoValue<'a>>>(&mut self, document: T, path: JsonPath) {
+		self.1 = Some(Filter::JsonContains(document.into(), path));
+	}
+
+	pub fn json_member_of<T: Into<RepoValue<'a>>>(&mut self, value: T, path: JsonPath) {
+		self.1 = Some(Filter::JsonMemberOf(value.into(), path));
+	}
+
 	pub fn to_named_filter(&'a self) -> Option<NamedFilter<'a>> {
 		self.filter()
 			.map(|f| NamedFilter::new(self.name(), f.clone()))
@@ -0,0 +1,157 @@
+use std::sync::Mutex;
+
+use uuid::{NoContext, Timestamp, Uuid};
+
+use crate::now;
+
+/// Generates the keys of entities that are not assigned by the database.
+/// The generators based on time read the clock of the crate, so that they follow `set_clock()` and `set_thread_clock()`.
+pub trait IdGenerator: Send + Sync {
+	type Id;
+
+	/// # Panics
+	///
+	/// The random generators, `UuidV4`, `UuidV7` and `Ulid`, panic if the OS has no source of randomness,
+	/// which `getrandom` only reports on unsupported targets or when the OS fails, e.g. without `/dev/urandom`.
+	/// An ID without its random bits would not be unique, so there is no fallback; `Snowflake` needs no randomness.
+	fn generate(&self) -> Self::Id;
+}
+
+/// milliseconds since the Unix epoch, according to the clock of the crate
+fn now_millis() -> u64 {
+	now().and_utc().timestamp_millis().max(0) as u64
+}
+
+/// panics without a source of randomness, see `IdGenerator::generate()`
+fn random_bytes<const N: usize>() -> [u8; N] {
+	let mut bytes = [0u8; N];
+	getrandom::getrandom(&mut bytes).expect("no source of randomness");
+	bytes
+}
+
+/// Random UUIDs, e.g. `67e55044-10b1-426f-9247-bb680e5fe0c8`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UuidV4;
+
+impl IdGenerator for UuidV4 {
+	type Id = String;
+
+	fn generate(&self) -> String {
+		Uuid::new_v4().hyphenated().to_string()
+	}
+}
+
+/// UUIDs starting with the time in milliseconds, which keep the index of the key in insertion order.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UuidV7;
+
+impl IdGenerator for UuidV7 {
+	type Id = String;
+
+	fn generate(&self) -> String {
+		let now = now().and_utc();
+		let ts = Timestamp::from_unix(NoContext, now.timestamp().max(0) as u64, now.timestamp_subsec_nanos());
+		Uuid::new_v7(ts).hyphenated().to_string()
+	}
+}
+
+/// ULIDs: 48 bits of milliseconds and 80 random bits, in 26 characters of Crockford's base32.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ulid;
+
+const CROCKFORD_BASE32: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+impl IdGenerator for Ulid {
+	type Id = String;
+
+	fn generate(&self) -> String {
+		let random = random_bytes::<10>();
+		let mut value = (now_millis() as u128 & 0xFFFF_FFFF_FFFF) << 80;
+		for (i, byte) in random.iter().enumerate() {
+			value |= (*byte as u128) << (72 - i * 8);
+		}
+		(0..26).rev()
+			.map(|i| CROCKFORD_BASE32[((value >> (i * 5)) & 0x1F) as usize] as char)
+			.collect()
+	}
+}
+
+/// Snowflake-style IDs: 41 bits of milliseconds since `epoch`, 10 bits of node and a 12-bit sequence.
+/// More than 4096 IDs in the same millisecond borrow the next milliseconds, so that the IDs stay increasing.
+#[derive(Debug)]
+pub struct Snowflake {
+	node: u16,
+	epoch: u64,
+	state: Mutex<(u64, u16)>,
+}
+
+impl Snowflake {
+	/// 2010-11-04T01:42:54.657Z, the epoch of Twitter
+	pub const DEFAULT_EPOCH: u64 = 1_288_834_974_657;
+
+	pub const fn new(node: u16) -> Self {
+		Self::with_epoch(node, Self::DEFAULT_EPOCH)
+	}
+
+	/// `epoch` is in milliseconds since the Unix epoch
+	pub const fn with_epoch(node: u16, epoch: u64) -> Self {
+		assert!(node < 1024, "the node of a snowflake must fit in 10 bits");
+		Self { node, epoch, state: Mutex::new((0, 0)) }
+	}
+}
+
+impl IdGenerator for Snowflake {
+	type Id = u64;
+
+	fn generate(&self) -> u64 {
+		let millis = now_millis().saturating_sub(self.epoch);
+		let mut state = self.state.lock().unwrap();
+		let (last, sequence) = *state;
+		*state = if millis > last {
+			(millis, 0)
+		} else if sequence < 0xFFF {
+			(last, sequence + 1)
+		} else {
+			(last + 1, 0)
+		};
+		let (millis, sequence) = *state;
+		(millis & 0x1FF_FFFF_FFFF) << 22 | (self.node as u64) << 12 | sequence as u64
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::NaiveDate;
+
+	use crate::{with_clock, FixedClock};
+	use super::*;
+
+	fn clock() -> FixedClock {
+		FixedClock(NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(12, 0, 0).unwrap())
+	}
+
+	#[test]
+	fn snowflake_ids_increase_beyond_the_sequence_of_a_millisecond() {
+		let snowflake = Snowflake::new(5);
+		let ids = with_clock(clock(), || (0..5000).map(|_| snowflake.generate()).collect::<Vec<u64>>());
+		assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+		assert_eq!(ids[0] >> 12 & 0x3FF, 5);
+		// the 4097th ID borrows the next millisecond
+		assert_eq!((ids[4096] >> 22) - (ids[0] >> 22), 1);
+	}
+
+	#[test]
+	fn ulids_start_with_the_time() {
+		let (first, second) = with_clock(clock(), || (Ulid.generate(), Ulid.generate()));
+		assert_eq!(first.len(), 26);
+		assert_eq!(first[..10], second[..10]);
+		assert_ne!(first, second);
+	}
+
+	#[test]
+	fn uuids_are_hyphenated() {
+		assert_eq!(UuidV4.generate().len(), 36);
+		let uuid = with_clock(clock(), || UuidV7.generate());
+		assert_eq!(&uuid[14..15], "7");
+	}
+}
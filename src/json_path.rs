@@ -0,0 +1,125 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::FromStrError;
+
+/// A validated MySQL JSON path such as `$.a.b`, `$.tags[0]`, `$."key with space"` or `$**.id`.
+/// The grammar does not allow quotes or backslashes, so the path can be inlined as a string literal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath(String);
+
+impl JsonPath {
+	pub fn parse(path: &str) -> Result<Self, FromStrError> {
+		Self::validate(path).map_err(|reason| format!("Invalid JSON path {:?}: {}", path, reason))?;
+		Ok(JsonPath(path.to_string()))
+	}
+
+	pub fn root() -> Self {
+		JsonPath(String::from("$"))
+	}
+
+	pub fn is_root(&self) -> bool {
+		self.0 == "$"
+	}
+
+	pub fn as_str(&self) -> &str {
+		&self.0
+	}
+
+	/// returns the path as a quoted SQL string literal
+	pub fn sql_literal(&self) -> String {
+		format!("'{}'", self.0)
+	}
+
+	/// returns `JSON_EXTRACT(field, 'path')`, or the field itself for the root path
+	pub fn extract_expression(&self, field: &str) -> String {
+		if self.is_root() {
+			field.to_string()
+		} else {
+			format!("JSON_EXTRACT({field}, {})", self.sql_literal())
+		}
+	}
+
+	fn validate(path: &str) -> Result<(), &'static str> {
+		let mut rest = path.strip_prefix('$').ok_or("must start with '$'")?;
+		while !rest.is_empty() {
+			if let Some(r) = rest.strip_prefix("**") {
+				if r.is_empty() {
+					return Err("'**' must be followed by a path leg");
+				}
+				rest = r;
+			} else if let Some(r) = rest.strip_prefix('.') {
+				rest = Self::member(r)?;
+			} else if let Some(r) = rest.strip_prefix('[') {
+				let end = r.find(']').ok_or("unclosed '['")?;
+				let index = &r[..end];
+				let valid = index == "*" || index == "last" || (!index.is_empty() && index.chars().all(|c| c.is_ascii_digit()));
+				if !valid {
+					return Err("array index must be a number, 'last' or '*'");
+				}
+				rest = &r[end + 1..];
+			} else {
+				return Err("expected '.', '[' or '**'");
+			}
+		}
+		Ok(())
+	}
+
+	/// consumes a member name after '.', returning the remaining path
+	fn member(path: &str) -> Result<&str, &'static str> {
+		if let Some(r) = path.strip_prefix('*') {
+			return Ok(r);
+		}
+		if let Some(r) = path.strip_prefix('"') {
+			let end = r.find('"').ok_or("unclosed '\"'")?;
+			if end == 0 || r[..end].contains(['\\', '\'']) {
+				return Err("invalid quoted member name");
+			}
+			return Ok(&r[end + 1..]);
+		}
+		let end = path.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$')).unwrap_or(path.len());
+		if end == 0 || path.starts_with(|c: char| c.is_ascii_digit()) {
+			return Err("invalid member name");
+		}
+		Ok(&path[end..])
+	}
+}
+
+impl FromStr for JsonPath {
+	type Err = FromStrError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::parse(s)
+	}
+}
+
+impl Display for JsonPath {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.0)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn valid_paths_are_accepted() {
+		for path in ["$", "$.a.b", "$.tags[0]", "$.tags[last]", "$[*].id", "$.\"key with space\"", "$**.id", "$.a.*"] {
+			assert_eq!(JsonPath::parse(path).map(|p| p.to_string()).ok().as_deref(), Some(path), "{path}");
+		}
+	}
+
+	#[test]
+	fn invalid_paths_are_rejected() {
+		for path in ["", "a.b", "$.", "$.1a", "$[x]", "$[0", "$**", "$.\"a'b\"", "$.\"\"", "$.a'; DROP TABLE t; --"] {
+			assert!(JsonPath::parse(path).is_err(), "{path}");
+		}
+	}
+
+	#[test]
+	fn the_root_path_extracts_the_field_itself() {
+		assert_eq!(JsonPath::root().extract_expression("data"), "data");
+		assert_eq!("$.a".parse::<JsonPath>().unwrap().extract_expression("data"), "JSON_EXTRACT(data, '$.a')");
+	}
+}
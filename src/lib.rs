@@ -4,6 +4,15 @@ mod errors;
 mod repo_value;
 mod definable;
 mod filter;
+mod expr;
+mod json_path;
+mod relation;
+mod clock;
+mod id_gen;
+mod audit;
+mod transaction;
+mod routing;
+mod tenant;
 mod sql_helper;
 
 pub use types::*;
@@ -11,6 +20,15 @@ pub use errors::*;
 pub use repo_value::*;
 pub use definable::*;
 pub use filter::*;
+pub use expr::*;
+pub use json_path::*;
+pub use relation::*;
+pub use clock::*;
+pub use id_gen::*;
+pub use audit::*;
+pub use transaction::*;
+pub use routing::*;
+pub use tenant::*;
 pub use sql_helper::*;
 
 #[cfg(feature = "mysql_async_helper")]
@@ -1,9 +1,11 @@
+/// The expansion calls `paste::paste!`, so crates using this macro must depend on `paste` themselves.
 #[macro_export]
 macro_rules! repo_data_partial {
 	(
 		$(#[doc = $doc:expr])*
 		$(#[derive($($derive:ident),+)])* 
 		$(#[entity = $entity:ty])?
+		$(#[updated_at = $updated_at:ident])?
 		struct $name:ident {
 			$( $(#[doc = $doc_prop:expr])* $prop:ident : $ty_prop:ty ),+ $(,)?
 		}
@@ -27,6 +29,7 @@ macro_rules! repo_data_partial {
 		$crate::repo_data_partial!(@impl_default $name { $( $prop ),+ });
 		$crate::repo_data_partial!(@impl_into_sql_updates
 			$(#[entity = $entity])?
+			$(#[updated_at = $updated_at])?
 			$name { $( $prop : $ty_prop ),+ }
 		);
 	};
@@ -56,11 +59,24 @@ macro_rules! repo_data_partial {
 					self.$prop.set(value);
 					self
 				}
+
+				paste::paste! {
+					// higher-ranked so that non-nullable fields still compile, see `Nullable`
+					pub fn [< $prop _null>](mut self) -> Self
+					where for<'x> $ty_prop: $crate::Nullable {
+						self.$prop.set(<$ty_prop as $crate::Nullable>::null());
+						self
+					}
+					pub fn [< $prop _default>](mut self) -> Self {
+						self.$prop.set_default();
+						self
+					}
+				}
 			)+
 
 			pub fn is_empty(&self) -> bool {
 				$(
-					if self.$prop.is_defined() {
+					if !self.$prop.is_undefined() {
 						return false;
 					}
 				)+
@@ -81,7 +97,7 @@ macro_rules! repo_data_partial {
 		}
 	};
 
-	(@impl_into_sql_updates #[entity = $entity:ty] $name:ident { $( $prop:ident : $ty_prop:ty ),+ }) => {
+	(@impl_into_sql_updates #[entity = $entity:ty] $(#[updated_at = $updated_at:ident])? $name:ident { $( $prop:ident : $ty_prop:ty ),+ }) => {
 		impl $entity {
 			pub fn partial() -> $name {
 				$name::default()
@@ -92,13 +108,74 @@ macro_rules! repo_data_partial {
 			fn from(mut d: $name) -> $crate::SqlUpdates<'static, $entity> {
 				let mut updates = $crate::SqlUpdates::<$entity>::default();
 				$(
-					if let Some(value) = d.$prop.take() {
-						updates.push(stringify!($prop), value.clone(), move |a| { a.$prop = value; });
+					match std::mem::take(&mut d.$prop) {
+						$crate::Definable::Defined(value) => {
+							updates.push(stringify!($prop), value.clone(), move |a| { a.$prop = value; });
+						},
+						$crate::Definable::DbDefault => {
+							updates.push_default(stringify!($prop), |_| {});
+						},
+						$crate::Definable::Undefined => {},
 					}
 				)+
+				$(
+					if !updates.is_empty() && !updates.assigns(stringify!($updated_at)) {
+						let now = $crate::now();
+						updates.push(stringify!($updated_at), now, move |a| { a.$updated_at = now.into(); });
+					}
+				)?
 				updates
 			}
 		}
 	};
-	(@impl_into_sql_updates $name:ident $body:tt) => {}
+	(@impl_into_sql_updates $(#[updated_at = $updated_at:ident])? $name:ident $body:tt) => {}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::SqlUpdates;
+
+	#[derive(Debug, Default, PartialEq)]
+	struct Profile {
+		nickname: Option<String>,
+		bio: String,
+		visits: u32,
+	}
+
+	repo_data_partial!(
+		#[entity = Profile]
+		struct ProfilePatch {
+			nickname: Option<String>,
+			bio: String,
+			visits: u32,
+		}
+	);
+
+	#[test]
+	fn undefined_fields_are_left_out() {
+		assert!(Profile::partial().is_empty());
+		let updates = SqlUpdates::from(Profile::partial().visits(3));
+		assert_eq!(updates.expressions(), "visits=3");
+	}
+
+	#[test]
+	fn null_fields_are_assigned_null_and_cleared_on_apply() {
+		let mut profile = Profile { nickname: Some("bob".to_string()), ..Default::default() };
+		let updates = SqlUpdates::from(Profile::partial().nickname_null());
+		assert_eq!(updates.expressions(), "nickname=NULL");
+		assert!(updates.dataset().is_empty());
+
+		updates.apply(&mut profile);
+		assert_eq!(profile.nickname, None);
+	}
+
+	#[test]
+	fn default_fields_are_assigned_default_and_left_unchanged_on_apply() {
+		let mut profile = Profile { bio: "hi".to_string(), ..Default::default() };
+		let updates = SqlUpdates::from(Profile::partial().bio_default().visits(1));
+		assert_eq!(updates.expressions(), "bio=DEFAULT, visits=1");
+
+		updates.apply(&mut profile);
+		assert_eq!(profile, Profile { nickname: None, bio: "hi".to_string(), visits: 1 });
+	}
 }
@@ -1,13 +1,28 @@
+/// The expansion calls `paste::paste!`, so crates using this macro must depend on `paste` themselves.
+///
+/// A key with `#[key_gen = generator]` gets a `generate_<key>()` function. `create(data)`, which builds the entity
+/// with a generated key, is only generated for entities with a single key and a named data struct (`data: Data { .. }`),
+/// since it takes that struct; the other entities call `generate_<key>()` for the key themselves.
 #[macro_export]
 macro_rules! repo_entity {
 	(
 		$(#[doc = $doc:expr])*
 		$(#[derive($($derive:ident),+)])*
+		$(#[table_name = $table:literal])?
+		$(#[version = $version:ident])?
+		$(#[soft_delete = $soft_delete:ident])?
+		$(#[tenant = $tenant:ident])?
+		$(#[created_at = $created_at:ident])?
+		$(#[updated_at = $updated_at:ident])?
 		$(#[repo_filter = $filter:ident])?
 		$(#[repo_partial = $partial:ident])?
+		$(#[searchable($($search:ident),+ $(,)?)])?
+		$(#[unique($($unique:ident),+ $(,)?)])*
+		$(#[belongs_to($bt_rel:ident : $bt_target:ident via $bt_via:ident)])*
+		$(#[has_many($hm_rel:ident : $hm_target:ident via $hm_via:ident)])*
 		struct $name:ident {
 			keys {
-				$( $(#[doc = $doc_key:expr])* $key:ident : $ty_key:ty ),+ $(,)?
+				$( $(#[doc = $doc_key:expr])* $(#[key_gen = $key_gen:expr])? $key:ident : $ty_key:ty ),+ $(,)?
 			},
 			data $(: $data:ident)? {
 				$( $(#[doc = $doc_prop:expr])* $prop:ident : $ty_prop:ty ),+ $(,)?
@@ -27,8 +42,35 @@ macro_rules! repo_entity {
 			keys { $( $(#[doc = $doc_key])* $key : $ty_key ),+ }
 		);
 
+		$crate::repo_entity!(@impl_table
+			$( #[table_name = $table] )?
+			$( #[version = $version] )?
+			$( #[soft_delete = $soft_delete] )?
+			$( #[tenant = $tenant] )?
+			$( #[created_at = $created_at] )?
+			$( #[updated_at = $updated_at] )?
+			$( #[unique($($unique),+)] )*
+			$name { $( $key ),+ , $( $prop ),+ }
+			keys { $( $key ),+ }
+		);
+
+		$crate::repo_entity!(@impl_diff $name { $( $prop : $ty_prop ),+ });
+
+		$crate::repo_entity!(@key_gen $name $(, data: $data)?,
+			keys { $( $(#[key_gen = $key_gen])? $key : $ty_key ),+ }
+		);
+
+		$(
+			$crate::repo_entity!(@belongs_to $name, $bt_rel : $bt_target via $bt_via);
+		)*
+		$(
+			$crate::repo_entity!(@has_many $name, $hm_rel : $hm_target via $hm_via);
+		)*
+
 		$crate::repo_entity!(@define_data_struct
 			#[entity = $name]
+			$( #[created_at = $created_at] )?
+			$( #[updated_at = $updated_at] )?
 			keys {
 				$( $(#[doc = $doc_key])* $key : $ty_key ),+
 			},
@@ -40,6 +82,8 @@ macro_rules! repo_entity {
 		$crate::repo_entity!(@repo_filter
 			$( #[repo_filter = $filter] )?
 			#[entity = $name]
+			$( #[searchable($($search),+)] )?
+			$( #[soft_delete = $soft_delete] )?
 			{
 				$( $(#[doc = $doc_key])* $key : $ty_key ),+ ,
 				$( $(#[doc = $doc_prop])* $prop : $ty_prop ),+
@@ -49,6 +93,7 @@ macro_rules! repo_entity {
 		$crate::repo_entity!(@repo_partial
 			$( #[repo_partial = $partial] )?
 			#[entity = $name]
+			$( #[updated_at = $updated_at] )?
 			{
 				$( $(#[doc = $doc_prop])* $prop : $ty_prop ),+
 			}
@@ -83,7 +128,7 @@ macro_rules! repo_entity {
 				self.$key.clone()
 			}
 
-			fn not_found(key: Self::Key) -> $crate::EntityNotFoundError<Self::Key> {
+			fn not_found(key: Self::Key) -> $crate::EntityNotFoundError {
 				$crate::EntityNotFoundError::new(stringify!($name), key)
 			}
 		}
@@ -98,14 +143,221 @@ macro_rules! repo_entity {
 				( $(self.$key.clone()),+ )
 			}
 
-			fn not_found(key: Self::Key) -> $crate::EntityNotFoundError<Self::Key> {
+			fn not_found(key: Self::Key) -> $crate::EntityNotFoundError {
 				$crate::EntityNotFoundError::new(stringify!($name), key)
 			}
 		}
 	};
 
+	(@impl_table
+		#[table_name = $table:literal]
+		$( #[version = $version:ident] )?
+		$( #[soft_delete = $soft_delete:ident] )?
+		$( #[tenant = $tenant:ident] )?
+		$( #[created_at = $created_at:ident] )?
+		$( #[updated_at = $updated_at:ident] )?
+		$( #[unique($($unique:ident),+)] )*
+		$name:ident { $( $field:ident ),+ }
+		keys { $( $key:ident ),+ }
+	) => {
+		impl $crate::Table for $name {
+			const TABLE_NAME: &'static str = $table;
+			const TABLE_FIELDS: &'static str = stringify!($($field),+);
+			const KEY_FIELDS: &'static str = stringify!($($key),+);
+			const ENTITY_NAME: &'static str = stringify!($name);
+			$( const VERSION_FIELD: Option<&'static str> = Some(stringify!($version)); )?
+			$( const SOFT_DELETE_FIELD: Option<&'static str> = Some(stringify!($soft_delete)); )?
+			$( const TENANT_FIELD: Option<&'static str> = Some(stringify!($tenant)); )?
+			$( const CREATED_AT_FIELD: Option<&'static str> = Some(stringify!($created_at)); )?
+			const UNIQUE_KEYS: &'static [&'static [&'static str]] = &[ $( &[ $( stringify!($unique) ),+ ] ),* ];
+
+			fn field_value(&self, field: &str) -> Option<$crate::RepoValue<'static>> {
+				$(
+					if field == stringify!($field) {
+						return Some(self.$field.clone().into());
+					}
+				)+
+				None
+			}
+
+			fn key_filter(&self) -> $crate::SqlFilter<'static> {
+				$crate::SqlFilter::default()
+					$(
+						.with(stringify!($key), &$crate::Filter::Equal(self.$key.clone()))
+					)+
+					$(
+						.with(stringify!($tenant), &$crate::Filter::Equal(self.$tenant.clone()))
+					)?
+			}
+
+			$(
+				fn update_filter(&self) -> $crate::SqlFilter<'static> {
+					self.key_filter()
+						.with(stringify!($version), &$crate::Filter::Equal(self.$version.clone()))
+				}
+			)?
+
+			fn touch_inserted(&mut self) {
+				$crate::repo_entity!(@stamp_null self, [$($created_at)?] [$($updated_at)?]);
+			}
+
+			#[allow(unused_variables)]
+			fn touch_updates(updates: &mut $crate::SqlUpdates<'_, Self>) {
+				$(
+					updates.increment(stringify!($version), 1, |entity: &mut Self| &mut entity.$version);
+				)?
+				$(
+					$crate::repo_entity!(@touch_updated_at updates, $updated_at);
+				)?
+			}
+
+			$(
+				fn mark_deleted(updates: &mut $crate::SqlUpdates<'_, Self>) {
+					let now = $crate::now();
+					updates.push(stringify!($soft_delete), now, move |entity: &mut Self| {
+						entity.$soft_delete = now.into();
+					});
+				}
+
+				fn mark_restored(updates: &mut $crate::SqlUpdates<'_, Self>) {
+					updates.push(stringify!($soft_delete), $crate::RepoValue::Null, |entity: &mut Self| {
+						entity.$soft_delete = $crate::Nullable::null();
+					});
+				}
+			)?
+
+			fn lost_update(key: Self::Key) -> $crate::OptimisticLockError {
+				$crate::OptimisticLockError::new(stringify!($name), key)
+			}
+		}
+	};
+	(@impl_table
+		$( #[version = $version:ident] )?
+		$( #[soft_delete = $soft_delete:ident] )?
+		$( #[tenant = $tenant:ident] )?
+		$( #[created_at = $created_at:ident] )?
+		$( #[updated_at = $updated_at:ident] )?
+		$( #[unique($($unique:ident),+)] )*
+		$name:ident $fields:tt keys $keys:tt
+	) => {};
+
+	(@touch_updated_at $updates:ident, $updated_at:ident) => {
+		if !$updates.assigns(stringify!($updated_at)) {
+			let now = $crate::now();
+			$updates.push(stringify!($updated_at), now, move |entity: &mut Self| {
+				entity.$updated_at = now.into();
+			});
+		}
+	};
+
+	(@stamp_values $values:ident [] []) => {};
+	(@stamp_values $values:ident [$($created_at:ident)?] [$($updated_at:ident)?]) => {
+		let now = $crate::now();
+		$( $values.set_if_null(stringify!($created_at), now); )?
+		$( $values.set_if_null(stringify!($updated_at), now); )?
+	};
+
+	(@stamp_null $entity:expr, [] []) => {};
+	(@stamp_null $entity:expr, [$($created_at:ident)?] [$($updated_at:ident)?]) => {
+		let now = $crate::now();
+		$(
+			if $crate::RepoValue::from($entity.$created_at.clone()) == $crate::RepoValue::Null {
+				$entity.$created_at = now.into();
+			}
+		)?
+		$(
+			if $crate::RepoValue::from($entity.$updated_at.clone()) == $crate::RepoValue::Null {
+				$entity.$updated_at = now.into();
+			}
+		)?
+	};
+
+	(@stamp_entity $entity:ident [] []) => {};
+	(@stamp_entity $entity:ident [$($created_at:ident)?] [$($updated_at:ident)?]) => {
+		let now = $crate::now();
+		$( $entity.$created_at = now.into(); )?
+		$( $entity.$updated_at = now.into(); )?
+	};
+
+	(@impl_diff $name:ident { $( $prop:ident : $ty_prop:ty ),+ }) => {
+		impl $name {
+			/// returns the updates turning `old` into `new`, which assign only the data fields that differ
+			// higher-ranked so that entities without PartialEq fields still compile, see `Nullable`
+			pub fn diff(old: &Self, new: &Self) -> $crate::SqlUpdates<'static, Self>
+			where $( for<'x> $ty_prop: PartialEq ),+ {
+				let mut updates = $crate::SqlUpdates::default();
+				$(
+					if old.$prop != new.$prop {
+						let value = new.$prop.clone();
+						updates.push(stringify!($prop), new.$prop.clone(), move |entity: &mut Self| { entity.$prop = value; });
+					}
+				)+
+				updates
+			}
+		}
+	};
+
+	// `create()` only for a single generated key and a named data struct, the other entities fall through to the next arm
+	(@key_gen $name:ident, data: $data:ident,
+		keys { #[key_gen = $key_gen:expr] $key:ident : $ty_key:ty }
+	) => {
+		$crate::repo_entity!(@key_gen $name, keys { #[key_gen = $key_gen] $key : $ty_key });
+
+		impl $name {
+			/// builds a new entity with a generated key, which is therefore known before the insert
+			pub fn create(data: $data) -> $name {
+				paste::paste! {
+					Self::build(Self::[<generate_ $key>](), data)
+				}
+			}
+		}
+	};
+	(@key_gen $name:ident $(, data: $data:ident)?,
+		keys { $( $(#[key_gen = $key_gen:expr])? $key:ident : $ty_key:ty ),+ }
+	) => {
+		paste::paste! {
+			impl $name {
+				$(
+					$(
+						/// the generator is built once, so that its state, like the sequence of a snowflake, is kept across calls
+						pub fn [<generate_ $key>]() -> $ty_key {
+							static GENERATOR: std::sync::OnceLock<Box<dyn $crate::IdGenerator<Id = $ty_key>>> = std::sync::OnceLock::new();
+							GENERATOR.get_or_init(|| Box::new($key_gen)).generate()
+						}
+					)?
+				)+
+			}
+		}
+	};
+
+	(@belongs_to $name:ident, $rel:ident : $target:ident via $via:ident) => {
+		impl $name {
+			pub fn $rel() -> $crate::Relation<$name, $target, <$target as $crate::Entity>::Key> {
+				$crate::Relation::new(
+					const { $crate::single_key_column(<$target as $crate::Table>::KEY_FIELDS) },
+					|parent: &$name| parent.$via.clone(),
+					|child: &$target| $crate::Entity::get_key(child),
+				)
+			}
+		}
+	};
+
+	(@has_many $name:ident, $rel:ident : $target:ident via $via:ident) => {
+		impl $name {
+			pub fn $rel() -> $crate::Relation<$name, $target, <$name as $crate::Entity>::Key> {
+				$crate::Relation::new(
+					stringify!($via),
+					|parent: &$name| $crate::Entity::get_key(parent),
+					|child: &$target| child.$via.clone(),
+				)
+			}
+		}
+	};
+
 	(@define_data_struct
 		#[entity = $entity:ident]
+		$( #[created_at = $created_at:ident] )?
+		$( #[updated_at = $updated_at:ident] )?
 		keys {
 			$( $(#[doc = $doc_key:expr])* $key:ident : $ty_key:ty ),+
 		},
@@ -122,11 +374,15 @@ macro_rules! repo_entity {
 		}
 
 		impl $entity {
+			/// the timestamp fields, if any, are set to the time of the clock
 			pub fn build($($key: $ty_key,)+ data: $data) -> $entity {
-				$entity {
+				#[allow(unused_mut)]
+				let mut entity = $entity {
 					$( $key, )+
 					$( $prop : data.$prop, )+
-				}
+				};
+				$crate::repo_entity!(@stamp_entity entity [$($created_at)?] [$($updated_at)?]);
+				entity
 			}
 		}
 
@@ -138,17 +394,36 @@ macro_rules! repo_entity {
 			}
 		}
 
+		/// the values of the whole entity, for inserting it with a key known beforehand
+		impl<'a> From<&'a $entity> for $crate::SqlValues<'a> {
+			fn from(entity: &'a $entity) -> $crate::SqlValues<'a> {
+				$crate::SqlValues::default()
+					$(
+						.with(stringify!($key), entity.$key.clone())
+					)+
+					$(
+						.with(stringify!($prop), entity.$prop.clone())
+					)+
+			}
+		}
+
+		/// the timestamp fields, if any, are set to the time of the clock when they are NULL
 		impl<'a> From<&'a $data> for $crate::SqlValues<'a> {
 			fn from(data: &'a $data) -> $crate::SqlValues<'a> {
-				$crate::SqlValues::default()
+				#[allow(unused_mut)]
+				let mut values = $crate::SqlValues::default()
 					$(
 						.with(stringify!($prop), data.$prop.clone())
-					)+
+					)+;
+				$crate::repo_entity!(@stamp_values values [$($created_at)?] [$($updated_at)?]);
+				values
 			}
 		}
 	};
 	(@define_data_struct
 		#[entity = $entity:ident]
+		$( #[created_at = $created_at:ident] )?
+		$( #[updated_at = $updated_at:ident] )?
 		keys {
 			$( $(#[doc = $doc_key:expr])* $key:ident : $ty_key:ty ),+
 		},
@@ -156,6 +431,7 @@ macro_rules! repo_entity {
 			$( $(#[doc = $doc_prop:expr])* $prop:ident : $ty_prop:ty ),+
 		}
 	) => {
+		/// the values of the whole entity, whose timestamps are set by `Table::touch_inserted()`
 		impl<'a> From<&'a $entity> for $crate::SqlValues<'a> {
 			fn from(entity: &'a $entity) -> $crate::SqlValues<'a> {
 				$crate::SqlValues::default()
@@ -172,6 +448,8 @@ macro_rules! repo_entity {
 	(@repo_filter
 		#[repo_filter = $filter:ident]
 		#[entity = $name:ident]
+		$( #[searchable($($search:ident),+)] )?
+		$( #[soft_delete = $soft_delete:ident] )?
 		{
 			$( $(#[doc = $doc_prop:expr])* $prop:ident : $ty_prop:ty ),+ $(,)?
 		}
@@ -179,16 +457,19 @@ macro_rules! repo_entity {
 		$crate::repo_filter!(
 			#[derive(Clone)]
 			#[entity = $name]
+			$( #[searchable($($search),+)] )?
+			$( #[soft_delete = $soft_delete] )?
 			struct $filter<'a> {
 				$( $(#[doc = $doc_prop])* $prop : $ty_prop ),+
 			}
 		);
 	};
-	(@repo_filter #[entity = $name:ident] $body:tt) => {};
+	(@repo_filter #[entity = $name:ident] $( #[searchable $search:tt] )? $( #[soft_delete = $soft_delete:ident] )? { $($body:tt)* }) => {};
 
 	(@repo_partial
 		#[repo_partial = $partial:ident]
 		#[entity = $name:ident]
+		$( #[updated_at = $updated_at:ident] )?
 		{
 			$( $(#[doc = $doc_prop:expr])* $prop:ident : $ty_prop:ty ),+ $(,)?
 		}
@@ -196,10 +477,185 @@ macro_rules! repo_entity {
 		$crate::repo_data_partial!(
 			#[derive(Debug)]
 			#[entity = $name]
+			$( #[updated_at = $updated_at] )?
 			struct $partial {
 				$( $(#[doc = $doc_prop])* $prop : $ty_prop ),+
 			}
 		);
 	};
-	(@repo_partial #[entity = $name:ident] $body:tt) => {};
+	(@repo_partial #[entity = $name:ident] $( #[updated_at = $updated_at:ident] )? { $($body:tt)* }) => {};
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::{NaiveDate, NaiveDateTime};
+
+	use crate::{set_thread_clock, FixedClock, RepoValue, SqlFilter, SqlUpdates, SqlValues, Table, UpdateStatement};
+
+	fn at(hour: u32) -> NaiveDateTime {
+		NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+	}
+
+	fn value<'v>(values: &'v SqlValues<'_>, field: &str) -> Option<&'v RepoValue<'v>> {
+		values.iter().find(|(f, _)| *f == field).map(|(_, v)| v)
+	}
+
+	repo_entity!(
+		#[derive(Debug, Clone, PartialEq)]
+		#[table_name = "posts"]
+		#[created_at = created_at]
+		#[updated_at = updated_at]
+		struct Post {
+			keys { id: u64 },
+			data: PostData {
+				title: String,
+				created_at: Option<NaiveDateTime>,
+				updated_at: Option<NaiveDateTime>,
+			}
+		}
+	);
+
+	#[test]
+	fn build_stamps_the_timestamps() {
+		let _clock = set_thread_clock(FixedClock(at(8)));
+		let post = Post::build(1, PostData { title: "hi".to_string(), ..Default::default() });
+		assert_eq!(post.created_at, Some(at(8)));
+		assert_eq!(post.updated_at, Some(at(8)));
+	}
+
+	#[test]
+	fn data_values_stamp_only_null_timestamps() {
+		let _clock = set_thread_clock(FixedClock(at(8)));
+		let data = PostData { title: "hi".to_string(), created_at: Some(at(1)), updated_at: None };
+		let values = SqlValues::from(&data);
+		assert_eq!(value(&values, "created_at"), Some(&RepoValue::DateTime(at(1))));
+		assert_eq!(value(&values, "updated_at"), Some(&RepoValue::DateTime(at(8))));
+	}
+
+	#[test]
+	fn touch_inserted_stamps_only_null_timestamps() {
+		let _clock = set_thread_clock(FixedClock(at(8)));
+		let mut post = Post { id: 1, title: "hi".to_string(), created_at: Some(at(1)), updated_at: None };
+		post.touch_inserted();
+		assert_eq!(post.created_at, Some(at(1)));
+		assert_eq!(post.updated_at, Some(at(8)));
+	}
+
+	#[test]
+	fn updates_touch_updated_at_but_never_created_at() {
+		let _clock = set_thread_clock(FixedClock(at(9)));
+		let mut post = Post { id: 1, title: "hi".to_string(), created_at: Some(at(1)), updated_at: Some(at(1)) };
+		let mut updates = SqlUpdates::default();
+		updates.push("title", "hello", |post: &mut Post| post.title = "hello".to_string());
+
+		let statement = UpdateStatement::for_entity(&post, updates);
+		assert_eq!(statement.expressions(), "UPDATE posts SET title='hello', updated_at='2024-05-01 09:00:00' WHERE id=1");
+		statement.finish(&crate::UpdateResult(1), &mut post).unwrap();
+		assert_eq!(post.created_at, Some(at(1)));
+		assert_eq!(post.updated_at, Some(at(9)));
+	}
+
+	#[test]
+	fn upsert_never_overwrites_created_at() {
+		let post = Post { id: 1, title: "hi".to_string(), created_at: Some(at(1)), updated_at: Some(at(1)) };
+		assert_eq!(Post::CREATED_AT_FIELD, Some("created_at"));
+		assert_eq!(SqlValues::from(&post).upsert_expressions::<Post>(), "title=VALUES(title), updated_at=VALUES(updated_at)");
+	}
+
+	repo_entity!(
+		#[derive(Debug, Clone, PartialEq)]
+		#[table_name = "documents"]
+		#[soft_delete = deleted_at]
+		#[repo_filter = DocumentFilter]
+		struct Document {
+			keys { id: u64 },
+			data { title: String, deleted_at: Option<NaiveDateTime> }
+		}
+	);
+
+	repo_entity!(
+		#[derive(Debug, Clone, PartialEq)]
+		#[table_name = "attachments"]
+		#[soft_delete = deleted_at]
+		struct Attachment {
+			keys { id: u64 },
+			data { deleted_at: Option<NaiveDateTime> }
+		}
+	);
+
+	#[test]
+	fn soft_delete_filter_excludes_deleted_rows_by_default() {
+		assert_eq!(SqlFilter::from(&Document::filter()).expressions(), "deleted_at IS NULL");
+		assert_eq!(SqlFilter::from(&Document::filter().title("a".to_string())).expressions(), "title='a', deleted_at IS NULL");
+		assert_eq!(SqlFilter::from(&Document::filter().with_deleted()).expressions(), "");
+		assert_eq!(SqlFilter::from(&Document::filter().only_deleted()).expressions(), "deleted_at IS NOT NULL");
+	}
+
+	#[test]
+	fn soft_delete_and_restore_set_the_timestamp() {
+		let _clock = set_thread_clock(FixedClock(at(10)));
+		let mut attachment = Attachment { id: 4, deleted_at: None };
+		assert_eq!(Attachment::SOFT_DELETE_FIELD, Some("deleted_at"));
+
+		let delete = UpdateStatement::soft_delete(&attachment);
+		assert_eq!(delete.expressions(), "UPDATE attachments SET deleted_at='2024-05-01 10:00:00' WHERE id=4");
+		delete.finish(&crate::UpdateResult(1), &mut attachment).unwrap();
+		assert_eq!(attachment.deleted_at, Some(at(10)));
+
+		let restore = UpdateStatement::restore(&attachment);
+		assert_eq!(restore.expressions(), "UPDATE attachments SET deleted_at=NULL WHERE id=4");
+		restore.finish(&crate::UpdateResult(1), &mut attachment).unwrap();
+		assert_eq!(attachment.deleted_at, None);
+	}
+
+	repo_entity!(
+		#[derive(Debug, Clone, PartialEq)]
+		#[table_name = "events"]
+		struct Event {
+			keys { #[key_gen = crate::Snowflake::new(1)] id: u64 },
+			data: EventData { name: String }
+		}
+	);
+
+	repo_entity!(
+		#[derive(Debug, Clone, PartialEq)]
+		#[table_name = "sessions"]
+		struct Session {
+			keys { #[key_gen = crate::UuidV7] id: String },
+			data: SessionData { user_id: u64 }
+		}
+	);
+
+	#[test]
+	fn generated_snowflake_keys_are_unique_within_a_millisecond() {
+		let _clock = set_thread_clock(FixedClock(at(11)));
+		let ids = (0..10_000).map(|_| Event::generate_id()).collect::<std::collections::HashSet<u64>>();
+		assert_eq!(ids.len(), 10_000);
+	}
+
+	#[test]
+	fn create_builds_the_entity_with_a_generated_key() {
+		let first = Event::create(EventData { name: "a".to_string() });
+		let second = Event::create(EventData { name: "b".to_string() });
+		assert_ne!(first.id, second.id);
+		assert_eq!(second.name, "b");
+
+		let session = Session::create(SessionData { user_id: 3 });
+		assert_eq!(session.id.len(), 36);
+		assert_eq!(session.user_id, 3);
+	}
+
+	#[test]
+	fn diff_assigns_only_the_changed_fields() {
+		let old = Post { id: 1, title: "draft".to_string(), created_at: Some(at(1)), updated_at: Some(at(1)) };
+		assert!(Post::diff(&old, &old.clone()).is_empty());
+
+		let new = Post { title: "final".to_string(), updated_at: Some(at(2)), ..old.clone() };
+		let updates = Post::diff(&old, &new);
+		assert_eq!(updates.expressions(), format!("title='final', updated_at='{}'", at(2)));
+
+		let mut post = old.clone();
+		updates.apply(&mut post);
+		assert_eq!(post, new);
+	}
 }
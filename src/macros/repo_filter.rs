@@ -4,6 +4,8 @@ macro_rules! repo_filter {
 		$(#[doc = $doc:expr])*
 		$(#[derive($($derive:ident),+)])* 
 		$(#[entity = $entity:ty])?
+		$(#[searchable($($search:ident),+ $(,)?)])?
+		$(#[soft_delete = $soft_delete:ident])?
 		struct $name:ident<$life:lifetime> {
 			$( $(#[doc = $doc_prop:expr])* $prop:ident : $($ty_life:lifetime)? $ty_prop:ty ),+ $(,)?
 		}
@@ -11,6 +13,7 @@ macro_rules! repo_filter {
 		$crate::repo_filter!(@define_struct
 			$(#[doc = $doc])*
 			$(#[derive($($derive),+)])* 
+			$(#[soft_delete = $soft_delete])?
 			struct $name<$life> {
 				$(
 					$(#[doc = $doc_prop])*
@@ -24,9 +27,12 @@ macro_rules! repo_filter {
 				$prop : $($ty_life)? $ty_prop
 			),+
 		});
-		$crate::repo_filter!(@impl_default $name { $( $prop ),+ });
+		$crate::repo_filter!(@impl_searchable $name { $($( $search ),+)? });
+		$crate::repo_filter!(@impl_soft_delete $name { $( $soft_delete )? });
+		$crate::repo_filter!(@impl_default $(#[soft_delete = $soft_delete])? $name { $( $prop ),+ });
 		$crate::repo_filter!(@impl_into_sql_filter
 			$(#[entity = $entity])?
+			$(#[soft_delete = $soft_delete])?
 			$name { $( $prop ),+ }
 		);
 	};
@@ -34,6 +40,7 @@ macro_rules! repo_filter {
 	(@define_struct
 		$(#[doc = $doc:expr])*
 		$(#[derive($($derive:ident),+)])* 
+		$(#[soft_delete = $soft_delete:ident])?
 		struct $name:ident<$life:lifetime> {
 			$( $(#[doc = $doc_prop:expr])* $prop:ident : $($ty_life:lifetime)? $ty_prop:ty ),+
 		}
@@ -47,8 +54,10 @@ macro_rules! repo_filter {
 			)+
 			pub limit: Option<usize>,
 			pub offset: Option<usize>,
+			$( pub scope: $crate::repo_filter!(@scope_type $soft_delete), )?
 		}
 	};
+	(@scope_type $soft_delete:ident) => { $crate::SoftDeleteScope };
 
 	(@impl_struct
 		$name:ident {
@@ -96,6 +105,18 @@ macro_rules! repo_filter {
 						self.$prop.between(value1, value2);
 						self
 					}
+					pub fn [< $prop _in_subquery>](mut self, subquery: $crate::Subquery<$crate::RepoValue<'a>>) -> Self {
+						self.$prop.in_subquery(subquery);
+						self
+					}
+					pub fn [< $prop _not_in_subquery>](mut self, subquery: $crate::Subquery<$crate::RepoValue<'a>>) -> Self {
+						self.$prop.not_in_subquery(subquery);
+						self
+					}
+					pub fn [< $prop _compare>](mut self, op: $crate::Comparison, rhs: $crate::Expr<$crate::RepoValue<'a>>) -> Self {
+						self.$prop.compare(op, rhs);
+						self
+					}
 				}
 			)+
 
@@ -111,7 +132,38 @@ macro_rules! repo_filter {
 		}
 	};
 
-	(@impl_default $name:ident { $( $prop:ident ),+ }
+	(@impl_searchable $name:ident { $( $search:ident ),+ }) => {
+		impl<'a> $name<'a> {
+			paste::paste! {
+				$(
+					pub fn [< $search _matches>](mut self, query: &'a str, mode: $crate::MatchMode) -> Self {
+						self.$search.matches(query, mode);
+						self
+					}
+				)+
+			}
+		}
+	};
+	(@impl_searchable $name:ident {}) => {};
+
+	(@impl_soft_delete $name:ident { $soft_delete:ident }) => {
+		impl $name<'_> {
+			/// includes the soft-deleted rows, which are excluded by default
+			pub fn with_deleted(mut self) -> Self {
+				self.scope = $crate::SoftDeleteScope::All;
+				self
+			}
+
+			/// matches only the soft-deleted rows
+			pub fn only_deleted(mut self) -> Self {
+				self.scope = $crate::SoftDeleteScope::Deleted;
+				self
+			}
+		}
+	};
+	(@impl_soft_delete $name:ident {}) => {};
+
+	(@impl_default $(#[soft_delete = $soft_delete:ident])? $name:ident { $( $prop:ident ),+ }
 	) => {
 		impl Default for $name<'_> {
 			fn default() -> Self {
@@ -121,12 +173,14 @@ macro_rules! repo_filter {
 					)+
 					limit: None,
 					offset: None,
+					$( scope: $crate::repo_filter!(@scope_default $soft_delete), )?
 				}
 			}
 		}
 	};
+	(@scope_default $soft_delete:ident) => { $crate::SoftDeleteScope::default() };
 
-	(@impl_into_sql_filter $(#[entity = $entity:ty])?  $name:ident { $( $prop:ident ),+ }) => {
+	(@impl_into_sql_filter $(#[entity = $entity:ty])? $name:ident { $( $prop:ident ),+ }) => {
 		impl<'a> From<&'a $name<'a>> for $crate::SqlFilter<'a> {
 			fn from(filter: &'a $name<'a>) -> $crate::SqlFilter<'a> {
 				$crate::SqlFilter::default()
@@ -138,6 +192,22 @@ macro_rules! repo_filter {
 
 		$crate::repo_filter!(@impl_entity_shortcut $(#[entity = $entity])? $name);
 	};
+	(@impl_into_sql_filter $(#[entity = $entity:ty])? #[soft_delete = $soft_delete:ident] $name:ident { $( $prop:ident ),+ }) => {
+		impl<'a> From<&'a $name<'a>> for $crate::SqlFilter<'a> {
+			fn from(filter: &'a $name<'a>) -> $crate::SqlFilter<'a> {
+				let sql_filter = $crate::SqlFilter::default()
+					$(
+						.with_named(&filter.$prop)
+					)+;
+				match filter.scope.filter::<$crate::RepoValue>() {
+					Some(scope) => sql_filter.with(stringify!($soft_delete), &scope),
+					None => sql_filter,
+				}
+			}
+		}
+
+		$crate::repo_filter!(@impl_entity_shortcut $(#[entity = $entity])? $name);
+	};
 
 	(@impl_entity_shortcut #[entity = $entity:ty] $name:ident) => {
 		impl $entity {
@@ -148,3 +218,35 @@ macro_rules! repo_filter {
 	};
 	(@impl_entity_shortcut $name:ident) => {};
 }
+
+#[cfg(test)]
+mod tests {
+	use crate::{Direction, MatchMode, SqlFilter, SqlOrder};
+
+	repo_filter!(
+		#[searchable(title, body)]
+		struct ArticleFilter<'a> {
+			title: &'a str,
+			body: &'a str,
+			author_id: u64,
+		}
+	);
+
+	#[test]
+	fn searchable_fields_match_full_text() {
+		let filter = ArticleFilter::default()
+			.title_matches("rust", MatchMode::Boolean)
+			.author_id(3);
+		assert_eq!(SqlFilter::from(&filter).expressions(), "MATCH(title) AGAINST ('rust' IN BOOLEAN MODE), author_id=3");
+	}
+
+	#[test]
+	fn matches_order_by_relevance() {
+		let filter = ArticleFilter::default().body_matches("async io", MatchMode::NaturalLanguage);
+		let order = SqlOrder::default()
+			.with_relevance(&filter.title, Direction::Desc)
+			.with_relevance(&filter.body, Direction::Desc)
+			.with("author_id", Direction::Asc);
+		assert_eq!(order.expressions(), "MATCH(body) AGAINST ('async io' IN NATURAL LANGUAGE MODE) DESC, author_id ASC");
+	}
+}
@@ -1,5 +1,13 @@
 mod mysql_helper;
+mod pool;
 mod query_object;
+mod router;
+mod tenant_query_object;
+mod transaction_manager;
 
 pub use mysql_helper::*;
+pub use pool::*;
 pub use query_object::*;
+pub use router::*;
+pub use tenant_query_object::*;
+pub use transaction_manager::*;
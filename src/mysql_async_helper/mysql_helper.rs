@@ -1,6 +1,6 @@
 use mysql_async::Value;
 
-use crate::{Filter, SqlFilter, SqlValues, SqlUpdates, RepoValue};
+use crate::{Filter, SqlFilter, SqlValues, SqlUpdates, SqlOrder, OrderBy, RepoValue, Subquery, Assignment, UpdateStatement};
 
 trait FromRepoValue {
 	fn from_repo_value(value: &'_ RepoValue<'_>) -> Self;
@@ -31,77 +31,155 @@ pub trait MySqlHelper {
 impl MySqlHelper for SqlFilter<'_> {
 	fn params(&self) -> Vec<(Vec<u8>, Value)> {
 		let mut params = Vec::<(Vec<u8>, Value)>::new();
-		for f in self.iter() {
-			let field = f.name();
-			let filter = f.filter();
-			match filter {
-				Filter::Equal(value) => {
-					params.push((Vec::<u8>::from(field), Value::from_repo_value(value)));
-				},
-				Filter::Not(value) => {
-					params.push((Vec::<u8>::from(field), Value::from_repo_value(value)));
-				},
-				Filter::LessorThan(value) => {
-					params.push((Vec::<u8>::from(field), Value::from_repo_value(value)));
-				},
-				Filter::EqualOrLessorThan(value) => {
-					params.push((Vec::<u8>::from(field), Value::from_repo_value(value)));
-				},
-				Filter::GreaterThan(value) => {
-					params.push((Vec::<u8>::from(field), Value::from_repo_value(value)));
-				},
-				Filter::EqualOrGreaterThan(value) => {
-					params.push((Vec::<u8>::from(field), Value::from_repo_value(value)));
-				},
-				Filter::In(v) => {
-					for (i, value) in v.iter().enumerate() {
-						params.push((Vec::<u8>::from(format!("{}_in_{}", field, i)), Value::from_repo_value(value)));
-					}
-				},
-				Filter::NotIn(v) => {
-					for (i, value) in v.iter().enumerate() {
-						params.push((Vec::<u8>::from(format!("{}_not_in_{}", field, i)), Value::from_repo_value(value)));
-					}
-				},
-				Filter::Between(v1, v2) => {
-					params.push((Vec::<u8>::from(format!("{}_between_0", field)), Value::from_repo_value(v1)));
-					params.push((Vec::<u8>::from(format!("{}_between_1", field)), Value::from_repo_value(v2)));
-				},
-			}
+		for (i, f) in self.iter().enumerate() {
+			push_filter_params(&mut params, "", i, f.name(), f.filter());
 		}
 		params
 	}
 
 	fn with_named_binding_holder(&self) -> String {
-		self.iter()
-			.map(|f| {
-				let field = f.name();
-				match f.filter() {
-					Filter::Equal(_) => format!("{field}=:{field}"),
-					Filter::Not(_) => format!("{field}<>:{field}"),
-					Filter::LessorThan(_) => format!("{field}<:{field}"),
-					Filter::EqualOrLessorThan(_) => format!("{field}<=:{field}"),
-					Filter::GreaterThan(_) => format!("{field}>:{field}"),
-					Filter::EqualOrGreaterThan(_) => format!("{field}>=:{field}"),
-					Filter::In(v) => {
-						let prefix = format!("{}_in", field);
-						let holders = comma_seperated_named_binding_holders(v.len(), &prefix);
-						format!("{field} IN ({holders})")
-					},
-					Filter::NotIn(v) => {
-						let prefix = format!("{}_not_in", field);
-						let holders = comma_seperated_named_binding_holders(v.len(), &prefix);
-						format!("{field} NOT IN ({holders})")
-					},
-					Filter::Between(_, _) => {
-						format!("{field} BETWEEN :{field}_between_0 AND :{field}_between_1")
-					},
-				}
-			})
+		self.iter().enumerate()
+			.map(|(i, f)| filter_named_binding_holder("", i, f.name(), f.filter()))
 			.collect::<Vec<String>>().join(" AND ")
 	}
 }
 
+/// parameters of a subquery are prefixed with the position of its filter, to keep them apart from the outer ones and from other subqueries.
+/// The prefix only depends on the shape of the filter, so that the same filter always renders the same statement.
+fn subquery_prefix(prefix: &str, index: usize) -> String {
+	format!("{prefix}sub{index}_")
+}
+
+/// `index` is the position of the filter among its siblings
+fn push_filter_params(params: &mut Vec<(Vec<u8>, Value)>, prefix: &str, index: usize, field: &str, filter: &Filter<RepoValue<'_>>) {
+	match filter {
+		Filter::Equal(value) => {
+			params.push((Vec::<u8>::from(format!("{prefix}{field}")), Value::from_repo_value(value)));
+		},
+		Filter::Not(value) => {
+			params.push((Vec::<u8>::from(format!("{prefix}{field}")), Value::from_repo_value(value)));
+		},
+		Filter::LessorThan(value) => {
+			params.push((Vec::<u8>::from(format!("{prefix}{field}")), Value::from_repo_value(value)));
+		},
+		Filter::EqualOrLessorThan(value) => {
+			params.push((Vec::<u8>::from(format!("{prefix}{field}")), Value::from_repo_value(value)));
+		},
+		Filter::GreaterThan(value) => {
+			params.push((Vec::<u8>::from(format!("{prefix}{field}")), Value::from_repo_value(value)));
+		},
+		Filter::EqualOrGreaterThan(value) => {
+			params.push((Vec::<u8>::from(format!("{prefix}{field}")), Value::from_repo_value(value)));
+		},
+		Filter::In(v) => {
+			for (i, value) in v.iter().enumerate() {
+				params.push((Vec::<u8>::from(format!("{prefix}{field}_in_{i}")), Value::from_repo_value(value)));
+			}
+		},
+		Filter::NotIn(v) => {
+			for (i, value) in v.iter().enumerate() {
+				params.push((Vec::<u8>::from(format!("{prefix}{field}_not_in_{i}")), Value::from_repo_value(value)));
+			}
+		},
+		Filter::Between(v1, v2) => {
+			params.push((Vec::<u8>::from(format!("{prefix}{field}_between_0")), Value::from_repo_value(v1)));
+			params.push((Vec::<u8>::from(format!("{prefix}{field}_between_1")), Value::from_repo_value(v2)));
+		},
+		Filter::IsNull | Filter::IsNotNull => {},
+		Filter::InSubquery(s) | Filter::NotInSubquery(s) | Filter::Exists(s) | Filter::NotExists(s) => {
+			let prefix = subquery_prefix(prefix, index);
+			for (i, (inner, filter)) in s.filters().enumerate() {
+				push_filter_params(params, &prefix, i, inner, filter);
+			}
+		},
+		Filter::Compare(lhs, _, rhs) => {
+			for (i, value) in lhs.values().into_iter().chain(rhs.values()).enumerate() {
+				params.push((Vec::<u8>::from(format!("{prefix}{field}_expr_{i}")), Value::from_repo_value(value)));
+			}
+		},
+		Filter::Raw(raw) => {
+			for (i, value) in raw.params().iter().enumerate() {
+				params.push((Vec::<u8>::from(format!("{prefix}raw{index}_{i}")), Value::from_repo_value(value)));
+			}
+		},
+		Filter::Match(m) => {
+			params.push((Vec::<u8>::from(format!("{prefix}{field}_match")), Value::from_repo_value(m.query())));
+		},
+		Filter::JsonExtract(_, _, value) => {
+			params.push((Vec::<u8>::from(format!("{prefix}{field}_json")), Value::from_repo_value(value)));
+		},
+		Filter::JsonContains(value, _) => {
+			params.push((Vec::<u8>::from(format!("{prefix}{field}_json")), Value::from_repo_value(value)));
+		},
+		Filter::JsonMemberOf(value, _) => {
+			params.push((Vec::<u8>::from(format!("{prefix}{field}_json")), Value::from_repo_value(value)));
+		},
+	}
+}
+
+fn filter_named_binding_holder(prefix: &str, index: usize, field: &str, filter: &Filter<RepoValue<'_>>) -> String {
+	match filter {
+		Filter::Equal(_) => format!("{field}=:{prefix}{field}"),
+		Filter::Not(_) => format!("{field}<>:{prefix}{field}"),
+		Filter::LessorThan(_) => format!("{field}<:{prefix}{field}"),
+		Filter::EqualOrLessorThan(_) => format!("{field}<=:{prefix}{field}"),
+		Filter::GreaterThan(_) => format!("{field}>:{prefix}{field}"),
+		Filter::EqualOrGreaterThan(_) => format!("{field}>=:{prefix}{field}"),
+		Filter::In(v) => {
+			let holders = comma_seperated_named_binding_holders(v.len(), &format!("{prefix}{field}_in"));
+			format!("{field} IN ({holders})")
+		},
+		Filter::NotIn(v) => {
+			let holders = comma_seperated_named_binding_holders(v.len(), &format!("{prefix}{field}_not_in"));
+			format!("{field} NOT IN ({holders})")
+		},
+		Filter::Between(_, _) => {
+			format!("{field} BETWEEN :{prefix}{field}_between_0 AND :{prefix}{field}_between_1")
+		},
+		Filter::IsNull => format!("{field} IS NULL"),
+		Filter::IsNotNull => format!("{field} IS NOT NULL"),
+		Filter::InSubquery(s) => {
+			format!("{field} IN ({})", subquery_named_binding_holder(prefix, index, s))
+		},
+		Filter::NotInSubquery(s) => {
+			format!("{field} NOT IN ({})", subquery_named_binding_holder(prefix, index, s))
+		},
+		Filter::Exists(s) => {
+			format!("EXISTS ({})", subquery_named_binding_holder(prefix, index, s))
+		},
+		Filter::NotExists(s) => {
+			format!("NOT EXISTS ({})", subquery_named_binding_holder(prefix, index, s))
+		},
+		Filter::Compare(lhs, op, rhs) => {
+			let mut i = 0;
+			op.render(lhs, rhs, &mut |_| {
+				let holder = format!(":{prefix}{field}_expr_{i}");
+				i += 1;
+				holder
+			})
+		},
+		Filter::Raw(raw) => raw.render(&mut |i| format!(":{prefix}raw{index}_{i}")),
+		Filter::Match(m) => m.render(&format!(":{prefix}{field}_match")),
+		Filter::JsonExtract(path, op, _) => {
+			format!("{}{}:{prefix}{field}_json", path.extract_expression(field), op.as_str())
+		},
+		Filter::JsonContains(_, path) => {
+			format!("JSON_CONTAINS({field}, :{prefix}{field}_json, {})", path.sql_literal())
+		},
+		Filter::JsonMemberOf(_, path) => {
+			format!(":{prefix}{field}_json MEMBER OF({})", path.extract_expression(field))
+		},
+	}
+}
+
+fn subquery_named_binding_holder(prefix: &str, index: usize, subquery: &Subquery<RepoValue<'_>>) -> String {
+	let prefix = subquery_prefix(prefix, index);
+	let conditions = subquery.filters().enumerate()
+		.map(|(i, (inner, filter))| filter_named_binding_holder(&prefix, i, inner, filter))
+		.collect();
+	subquery.select_expression(conditions)
+}
+
 /// returns ":{prefix}_0, :{prefix}_1, :{prefix}_2, ..."
 fn comma_seperated_named_binding_holders(len: usize, prefix: &str) -> String {
 	(0..len)
@@ -110,6 +188,30 @@ fn comma_seperated_named_binding_holders(len: usize, prefix: &str) -> String {
 		.join(", ")
 }
 
+impl MySqlHelper for SqlOrder<'_> {
+	fn params(&self) -> Vec<(Vec<u8>, Value)> {
+		self.iter()
+			.filter_map(|o| match o {
+				OrderBy::Field(_, _) => None,
+				OrderBy::Relevance(field, m, _) => {
+					Some((Vec::<u8>::from(format!("{field}_relevance")), Value::from_repo_value(m.query())))
+				},
+			})
+			.collect()
+	}
+
+	fn with_named_binding_holder(&self) -> String {
+		self.iter()
+			.map(|o| match o {
+				OrderBy::Field(field, direction) => format!("{field} {}", direction.as_str()),
+				OrderBy::Relevance(field, m, direction) => {
+					format!("{} {}", m.render(&format!(":{field}_relevance")), direction.as_str())
+				},
+			})
+			.collect::<Vec<String>>().join(", ")
+	}
+}
+
 impl MySqlHelper for SqlValues<'_> {
 	fn params(&self) -> Vec<(Vec<u8>, Value)> {
 		self.iter()
@@ -130,10 +232,155 @@ impl MySqlHelper for SqlValues<'_> {
 
 impl<E> MySqlHelper for SqlUpdates<'_, E> {
 	fn params(&self) -> Vec<(Vec<u8>, Value)> {
-		self.dataset().params()
+		let mut data = self.dataset().iter();
+		self.assignments()
+			.enumerate()
+			.filter(|(_, (_, assignment))| assignment.binds_value())
+			.filter_map(|(i, (field, assignment))| {
+				data.next().map(|(_, value)| {
+					(Vec::<u8>::from(update_param_name(i, field, assignment)), Value::from_repo_value(value))
+				})
+			})
+			.collect::<Vec<(Vec<u8>, Value)>>()
+	}
+
+	fn with_named_binding_holder(&self) -> String {
+		let assignments = self.assignments().collect::<Vec<_>>();
+		self.render(&mut |i, field| format!(":{}", update_param_name(i, field, &assignments[i].1)))
+	}
+}
+
+/// plain assignments are named after the field, the others also after their position
+fn update_param_name(i: usize, field: &str, assignment: &Assignment) -> String {
+	match assignment {
+		Assignment::Value => field.to_string(),
+		_ => format!("{field}_{i}"),
+	}
+}
+
+/// prefixes the parameters of the `WHERE` clause, so that they never collide with those of the `SET` clause
+const WHERE_PREFIX: &str = "where_";
+
+impl<E> MySqlHelper for UpdateStatement<'_, E> {
+	fn params(&self) -> Vec<(Vec<u8>, Value)> {
+		let mut params = self.updates().params();
+		for (i, f) in self.filter().iter().enumerate() {
+			push_filter_params(&mut params, WHERE_PREFIX, i, f.name(), f.filter());
+		}
+		params
 	}
 
+	/// returns the whole `UPDATE` statement
 	fn with_named_binding_holder(&self) -> String {
-		self.dataset().with_named_binding_holder()
+		let filter = self.filter().iter().enumerate()
+			.map(|(i, f)| filter_named_binding_holder(WHERE_PREFIX, i, f.name(), f.filter()))
+			.collect::<Vec<String>>().join(" AND ");
+		self.statement(self.updates().with_named_binding_holder(), filter)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashSet;
+
+	use crate::{repo_entity, KeysetScan};
+	use super::*;
+
+	repo_entity!(
+		#[table_name = "orders"]
+		struct Order {
+			keys { id: u64 },
+			data { total: i64 }
+		}
+	);
+
+	fn param_names(helper: &impl MySqlHelper) -> Vec<String> {
+		helper.params().into_iter().map(|(name, _)| String::from_utf8(name).unwrap()).collect()
+	}
+
+	/// every parameter has a distinct name, which the rendered statement refers to
+	fn assert_params_bound(helper: &impl MySqlHelper) {
+		let names = param_names(helper);
+		let sql = helper.with_named_binding_holder();
+		assert_eq!(names.iter().collect::<HashSet<_>>().len(), names.len(), "colliding parameters in {names:?}");
+		for name in &names {
+			assert!(sql.contains(&format!(":{name}")), "{name} is not bound in {sql}");
+		}
+	}
+
+	#[test]
+	fn exists_subqueries_on_the_same_table_have_distinct_params() {
+		let paid = Subquery::new("payments", "1", SqlFilter::default().with("status", &Filter::Equal("paid")))
+			.correlate("payments.order_id", "orders.id");
+		let refunded = Subquery::new("payments", "1", SqlFilter::default().with("status", &Filter::Equal("refunded")))
+			.correlate("payments.order_id", "orders.id");
+		let filter = SqlFilter::default().with_exists(paid).with_not_exists(refunded);
+
+		assert_params_bound(&filter);
+		assert_eq!(filter.params().into_iter().map(|(_, v)| v).collect::<Vec<_>>(), vec![Value::from("paid"), Value::from("refunded")]);
+	}
+
+	#[test]
+	fn in_subqueries_on_the_same_field_have_distinct_params() {
+		let filter = SqlFilter::default()
+			.with("customer_id", &Filter::<RepoValue>::InSubquery(Subquery::new("customers", "id", SqlFilter::default().with("country", &Filter::Equal("FR")))))
+			.with("customer_id", &Filter::<RepoValue>::NotInSubquery(Subquery::new("customers", "id", SqlFilter::default().with("country", &Filter::Equal("BE")))));
+
+		assert_params_bound(&filter);
+	}
+
+	#[test]
+	fn subqueries_render_the_same_statement_every_time() {
+		let filter = || SqlFilter::default()
+			.with("customer_id", &Filter::<RepoValue>::InSubquery(Subquery::new("customers", "id", SqlFilter::default().with("country", &Filter::Equal("FR")))));
+
+		assert_eq!(filter().with_named_binding_holder(), filter().with_named_binding_holder());
+		assert_eq!(filter().with_named_binding_holder(), "customer_id IN (SELECT id FROM customers WHERE country=:sub0_country)");
+	}
+
+	#[test]
+	fn nested_subqueries_have_distinct_params() {
+		let vip = Subquery::new("customers", "id", SqlFilter::default().with("country", &Filter::Equal("FR")));
+		let orders = Subquery::new("orders", "id", SqlFilter::default()
+			.with("country", &Filter::Equal("BE"))
+			.with("customer_id", &Filter::<RepoValue>::InSubquery(vip)));
+		let filter = SqlFilter::default()
+			.with("country", &Filter::Equal("NL"))
+			.with("order_id", &Filter::<RepoValue>::InSubquery(orders));
+
+		assert_params_bound(&filter);
+		assert_eq!(filter.with_named_binding_holder(),
+			"country=:country AND order_id IN (SELECT id FROM orders WHERE country=:sub1_country AND customer_id IN (SELECT id FROM customers WHERE country=:sub1_sub1_country))");
+	}
+
+	#[test]
+	fn keyset_cursors_have_distinct_params_from_the_filter_on_the_key() {
+		let mut scan = KeysetScan::<Order>::new(SqlFilter::default().with("id", &Filter::GreaterThan(1u64)), 2);
+		scan.advance(&[Order { id: 3, total: 1 }, Order { id: 5, total: 2 }]).unwrap();
+		let filter = scan.chunk_filter();
+
+		assert_params_bound(&filter);
+		assert_eq!(filter.params().into_iter().map(|(_, v)| v).collect::<Vec<_>>(), vec![Value::from(1u64), Value::from(5u64)]);
+	}
+
+	#[test]
+	fn consecutive_keyset_chunks_render_the_same_statement() {
+		let mut scan = KeysetScan::<Order>::new(SqlFilter::default().with("total", &Filter::GreaterThan(0i64)), 2);
+		scan.advance(&[Order { id: 3, total: 1 }, Order { id: 5, total: 2 }]).unwrap();
+		let second = scan.statement(scan.chunk_filter().with_named_binding_holder());
+		scan.advance(&[Order { id: 6, total: 1 }, Order { id: 9, total: 2 }]).unwrap();
+		let third = scan.statement(scan.chunk_filter().with_named_binding_holder());
+
+		assert_eq!(second, third);
+		assert_eq!(third, "SELECT id, total FROM orders WHERE total>:total AND (id > :raw1_0) ORDER BY id LIMIT 2");
+	}
+
+	#[test]
+	fn raw_fragments_of_merged_filters_have_distinct_params() {
+		let mut filter = SqlFilter::default().with_raw("price * quantity > ?", vec![100]).unwrap();
+		filter.extend(SqlFilter::default().with_raw("DATEDIFF(shipped_at, ordered_at) > ?", vec![3]).unwrap());
+
+		assert_params_bound(&filter);
+		assert_eq!(filter.with_named_binding_holder(), "(price * quantity > :raw0_0) AND (DATEDIFF(shipped_at, ordered_at) > :raw1_0)");
 	}
 }
@@ -0,0 +1,259 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use mysql_async::{Conn, Pool, Result, Transaction, TxOpts, prelude::Queryable};
+
+use crate::PoolTimeoutError;
+use super::QueryObject;
+
+/// Hands out the connections of a `mysql_async::Pool` as `QueryObject`s, which return to the pool when dropped.
+#[derive(Clone)]
+pub struct ConnectionPool {
+	pool: Pool,
+	checkout_timeout: Option<Duration>,
+	test_before_checkout: bool,
+	init_statements: Vec<String>,
+	counters: Arc<Counters>,
+}
+
+#[derive(Default)]
+struct Counters {
+	checkouts: AtomicU64,
+	timeouts: AtomicU64,
+	failed_health_checks: AtomicU64,
+	wait_micros: AtomicU64,
+	max_wait_micros: AtomicU64,
+}
+
+impl ConnectionPool {
+	pub fn new(pool: Pool) -> Self {
+		Self {
+			pool,
+			checkout_timeout: None,
+			test_before_checkout: false,
+			init_statements: Vec::new(),
+			counters: Arc::default(),
+		}
+	}
+
+	/// fails a checkout with a `PoolTimeoutError` if no connection is available within `timeout`
+	pub fn with_checkout_timeout(mut self, timeout: Duration) -> Self {
+		self.checkout_timeout = Some(timeout);
+		self
+	}
+
+	/// pings every connection before handing it out, and replaces it once if the ping fails
+	pub fn test_before_checkout(mut self) -> Self {
+		self.test_before_checkout = true;
+		self
+	}
+
+	/// adds a statement run on every checkout, since the pool resets the session of returned connections
+	pub fn with_init_statement(mut self, statement: impl Into<String>) -> Self {
+		self.init_statements.push(statement.into());
+		self
+	}
+
+	/// sets the time zone of the session, like `+00:00` or `Europe/Paris`
+	pub fn with_time_zone(self, time_zone: &str) -> Self {
+		self.with_init_statement(format!("SET time_zone = {}", quote(time_zone)))
+	}
+
+	/// sets the SQL mode of the session, like `STRICT_ALL_TABLES,NO_ZERO_DATE`
+	pub fn with_sql_mode(self, sql_mode: &str) -> Self {
+		self.with_init_statement(format!("SET sql_mode = {}", quote(sql_mode)))
+	}
+
+	pub fn pool(&self) -> &Pool {
+		&self.pool
+	}
+
+	/// checks out a connection, waiting at most the checkout timeout if any
+	pub async fn get(&self) -> Result<QueryObject<'static>> {
+		let conn = self.timed(self.checkout()).await?;
+		let mut query_object = QueryObject::Conn(conn);
+		for statement in &self.init_statements {
+			Queryable::query_drop(&mut query_object, statement).await?;
+		}
+		Ok(query_object)
+	}
+
+	/// begins a transaction on a connection checked out like with `get()`, with the same timeout, health check,
+	/// metrics and session setup; the init statements run right after `START TRANSACTION`
+	pub async fn start_transaction(&self, options: TxOpts) -> Result<Transaction<'static>> {
+		let mut tx = self.timed(self.begin(options)).await?;
+		for statement in &self.init_statements {
+			tx.query_drop(statement).await?;
+		}
+		Ok(tx)
+	}
+
+	/// checks out a connection and pings the server
+	pub async fn ping(&self) -> Result<()> {
+		self.get().await?.ping().await
+	}
+
+	/// returns the current size of the pool, and the counters of the checkouts made through this wrapper
+	pub fn metrics(&self) -> PoolMetrics {
+		let pool = self.pool.metrics();
+		PoolMetrics {
+			connections: pool.connection_count.load(Ordering::Relaxed),
+			idle_connections: pool.connections_in_pool.load(Ordering::Relaxed),
+			waiting: pool.active_wait_requests.load(Ordering::Relaxed),
+			checkouts: self.counters.checkouts.load(Ordering::Relaxed),
+			timeouts: self.counters.timeouts.load(Ordering::Relaxed),
+			failed_health_checks: self.counters.failed_health_checks.load(Ordering::Relaxed),
+			total_wait_time: Duration::from_micros(self.counters.wait_micros.load(Ordering::Relaxed)),
+			max_wait_time: Duration::from_micros(self.counters.max_wait_micros.load(Ordering::Relaxed)),
+		}
+	}
+
+	/// waits for `checkout` at most the checkout timeout if any, and records the wait
+	async fn timed<T>(&self, checkout: impl Future<Output = Result<T>>) -> Result<T> {
+		let start = Instant::now();
+		let checked_out = match self.checkout_timeout {
+			Some(timeout) => match tokio::time::timeout(timeout, checkout).await {
+				Ok(checked_out) => checked_out,
+				Err(_) => {
+					self.counters.timeouts.fetch_add(1, Ordering::Relaxed);
+					return Err(mysql_async::Error::Other(Box::new(PoolTimeoutError::new(timeout))));
+				},
+			},
+			None => checkout.await,
+		}?;
+		self.record_wait(start.elapsed());
+		Ok(checked_out)
+	}
+
+	async fn checkout(&self) -> Result<Conn> {
+		let mut conn = self.pool.get_conn().await?;
+		if self.test_before_checkout && conn.ping().await.is_err() {
+			self.counters.failed_health_checks.fetch_add(1, Ordering::Relaxed);
+			// the broken connection is not returned to the pool
+			let _ = conn.disconnect().await;
+			conn = self.pool.get_conn().await?;
+			conn.ping().await?;
+		}
+		Ok(conn)
+	}
+
+	async fn begin(&self, options: TxOpts) -> Result<Transaction<'static>> {
+		let mut tx = self.pool.start_transaction(options.clone()).await?;
+		if self.test_before_checkout && tx.ping().await.is_err() {
+			self.counters.failed_health_checks.fetch_add(1, Ordering::Relaxed);
+			// the pool drops the broken connection once the transaction is dropped
+			drop(tx);
+			tx = self.pool.start_transaction(options).await?;
+			tx.ping().await?;
+		}
+		Ok(tx)
+	}
+
+	fn record_wait(&self, wait: Duration) {
+		let micros = u64::try_from(wait.as_micros()).unwrap_or(u64::MAX);
+		self.counters.checkouts.fetch_add(1, Ordering::Relaxed);
+		self.counters.wait_micros.fetch_add(micros, Ordering::Relaxed);
+		self.counters.max_wait_micros.fetch_max(micros, Ordering::Relaxed);
+	}
+}
+
+impl From<Pool> for ConnectionPool {
+	fn from(pool: Pool) -> Self {
+		Self::new(pool)
+	}
+}
+
+/// A snapshot of the size of a `ConnectionPool` and of its checkouts.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct PoolMetrics {
+	/// the open connections, in the pool or checked out
+	pub connections: usize,
+	/// the open connections waiting in the pool
+	pub idle_connections: usize,
+	/// the checkouts waiting for a connection
+	pub waiting: usize,
+	/// the successful checkouts
+	pub checkouts: u64,
+	pub timeouts: u64,
+	/// the connections replaced because their ping failed
+	pub failed_health_checks: u64,
+	pub total_wait_time: Duration,
+	pub max_wait_time: Duration,
+}
+
+impl PoolMetrics {
+	pub fn average_wait_time(&self) -> Duration {
+		match self.checkouts {
+			0 => Duration::ZERO,
+			n => Duration::from_micros(u64::try_from(self.total_wait_time.as_micros()).unwrap_or(u64::MAX) / n),
+		}
+	}
+}
+
+fn quote(value: &str) -> String {
+	format!("'{}'", value.replace('\\', "\\\\").replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::net::TcpListener;
+
+	use crate::RepoError;
+	use super::*;
+	use super::super::{ReadWriteRouter, TransactionManager};
+
+	/// a pool of a server which accepts the connections but never answers, so that every checkout times out
+	fn silent_pool(listener: &TcpListener) -> ConnectionPool {
+		let url = format!("mysql://root@{}/test", listener.local_addr().unwrap());
+		ConnectionPool::new(Pool::new(url.as_str())).with_checkout_timeout(Duration::from_millis(50))
+	}
+
+	fn assert_timeout(error: mysql_async::Error) {
+		assert!(matches!(RepoError::from(error), RepoError::Timeout { .. }));
+	}
+
+	#[tokio::test]
+	async fn checkouts_time_out() {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let pool = silent_pool(&listener);
+
+		assert_timeout(pool.get().await.err().unwrap());
+		assert_eq!(pool.metrics().timeouts, 1);
+		assert_eq!(pool.metrics().checkouts, 0);
+	}
+
+	#[tokio::test]
+	async fn transactions_are_checked_out_through_the_pool() {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let pool = silent_pool(&listener);
+
+		assert_timeout(pool.start_transaction(TxOpts::default()).await.err().unwrap());
+		assert_timeout(TransactionManager::new(pool.clone()).begin().await.err().unwrap());
+		assert_timeout(ReadWriteRouter::new(pool.clone(), Vec::new()).transaction_manager().begin().await.err().unwrap());
+		assert_eq!(pool.metrics().timeouts, 3);
+	}
+
+	#[test]
+	fn average_wait_time_divides_by_the_checkouts() {
+		let metrics = PoolMetrics {
+			connections: 2,
+			idle_connections: 1,
+			waiting: 0,
+			checkouts: 4,
+			timeouts: 0,
+			failed_health_checks: 0,
+			total_wait_time: Duration::from_millis(10),
+			max_wait_time: Duration::from_millis(6),
+		};
+		assert_eq!(metrics.average_wait_time(), Duration::from_micros(2500));
+		assert_eq!(PoolMetrics { checkouts: 0, ..metrics }.average_wait_time(), Duration::ZERO);
+	}
+
+	#[test]
+	fn session_values_are_quoted() {
+		assert_eq!(quote("Europe/Paris"), "'Europe/Paris'");
+		assert_eq!(quote("it's"), "'it''s'");
+	}
+}
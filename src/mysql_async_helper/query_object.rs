@@ -1,8 +1,13 @@
-use mysql_async::{Conn, prelude::{Queryable, StatementLike, AsQuery}, Params, Result, Transaction, QueryResult, TextProtocol, Statement, BinaryProtocol};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use mysql_async::{Conn, prelude::{Queryable, StatementLike, AsQuery}, Params, Result, Transaction, QueryResult, TextProtocol, Statement, BinaryProtocol, ResultSetStream};
 use mysql_common::prelude::FromRow;
 use tokio::sync::MutexGuard;
 
-use crate::{UpdateResult, InsertResult};
+use crate::{UpdateResult, InsertResult, Relation, RepoValue, Table, UpdateStatement, Filter, Audit, AuditDestination, AuditRecord, Savepoint, NoTransactionError, SqlValues, DuplicateError, FromStrError, TenantScope, KeysetScan, is_duplicate_entry_code, check_tenant_filter, check_tenant_scope, check_tenant_values};
+use crate::errors::duplicate_key_index;
+use super::MySqlHelper;
 
 type BoxFuture<'a, T> = futures_core::future::BoxFuture<'a, Result<T>>;
 
@@ -68,6 +73,29 @@ impl Queryable for QueryObject<'_> {
 }
 
 impl QueryObject<'_> {
+	/// runs `f` in a nested transaction: a savepoint released if `f` succeeds, and rolled back to otherwise,
+	/// so that the enclosing transaction goes on if the caller handles the error
+	pub async fn transaction<T, F>(&mut self, f: F) -> Result<T>
+	where F: for<'t> FnOnce(&'t mut Self) -> BoxFuture<'t, T> {
+		if let QueryObject::Conn(_) = self {
+			return Err(mysql_async::Error::Other(Box::new(NoTransactionError)));
+		}
+
+		let savepoint = Savepoint::new();
+		Queryable::query_drop(self, savepoint.create_statement()).await?;
+		match f(self).await {
+			Ok(value) => {
+				Queryable::query_drop(self, savepoint.release_statement()).await?;
+				Ok(value)
+			},
+			Err(e) => {
+				// the error of `f` matters more than the one of the rollback
+				let _ = Queryable::query_drop(self, savepoint.rollback_statement()).await;
+				Err(e)
+			},
+		}
+	}
+
 	pub async fn query<'a, T, Q>(&'a mut self, query: Q) -> Result<Vec<T>>
 	where
 		Q: AsQuery + 'a,
@@ -76,6 +104,15 @@ impl QueryObject<'_> {
 		Queryable::query(self, query).await
 	}
 
+	/// streams the rows of `query` instead of collecting them, which holds the connection until the stream is dropped
+	pub async fn query_stream<'a, T, Q>(&'a mut self, query: Q) -> Result<ResultSetStream<'a, 'a, 'static, T, TextProtocol>>
+	where
+		Q: AsQuery + 'a,
+		T: FromRow + Send + Unpin + 'static,
+	{
+		Queryable::query_stream(self, query).await
+	}
+
 	pub async fn query_update<'a, Q>(&'a mut self, query: Q) -> Result<UpdateResult>
 	where
 		Q: AsQuery + 'a,
@@ -102,6 +139,16 @@ impl QueryObject<'_> {
 		Queryable::exec(self, stmt, params).await
 	}
 
+	/// streams the rows of `stmt` instead of collecting them, which holds the connection until the stream is dropped
+	pub async fn exec_stream<'a: 'b, 'b, T, S, P>(&'a mut self, stmt: S, params: P) -> Result<ResultSetStream<'a, 'a, 'static, T, BinaryProtocol>>
+	where
+		S: StatementLike + 'a,
+		P: Into<Params> + Send + 'b,
+		T: FromRow + Send + Unpin + 'static,
+	{
+		Queryable::exec_stream(self, stmt, params).await
+	}
+
 	pub async fn exec_first<'a: 'b, 'b, T, S, P>(&'a mut self, stmt: S, params: P) -> Result<Option<T>>
 	where
 		S: StatementLike + 'b,
@@ -125,9 +172,9 @@ impl QueryObject<'_> {
 		P: Into<Params>
 	{
 		let qr = Queryable::exec_iter(self, stmt, params).await?;
-		let id = qr.last_insert_id().unwrap();
+		let result = InsertResult::new(qr.last_insert_id(), qr.affected_rows(), qr.warnings());
 		qr.drop_result().await?;
-		Ok(InsertResult(id))
+		Ok(result)
 	}
 
 	pub async fn exec_update<'a: 's, 's, Q, P>(&'a mut self, stmt: Q, params: P) -> Result<UpdateResult>
@@ -140,4 +187,239 @@ impl QueryObject<'_> {
 		qr.drop_result().await?;
 		Ok(UpdateResult(affected_rows))
 	}
+
+	/// executes `statement`, and applies its updates to `target` once the expected number of rows has been affected.
+	/// If no row is changed while some are expected, the matched rows are counted and reported instead,
+	/// so that a row set to its current values is accepted like with the sqlx backend.
+	pub async fn exec_update_statement<E>(&mut self, statement: UpdateStatement<'_, E>, target: &mut E) -> Result<UpdateResult> {
+		if statement.updates().is_empty() {
+			return Ok(UpdateResult(0));
+		}
+		statement.check_tenant()
+			.map_err(|e| mysql_async::Error::Other(Box::new(e)))?;
+
+		let mut result = self.exec_update(statement.with_named_binding_holder(), statement.params()).await
+			.map_err(|e| translate_duplicate(e, |index| statement.duplicate(index)))?;
+		if statement.needs_matched_rows(&result) {
+			// the changed rows are counted unless the connection enables client_found_rows, so unchanged rows are counted here
+			let filter = statement.filter();
+			let matched: Option<u64> = self.exec_first(statement.count_statement(filter.with_named_binding_holder()), filter.params()).await?;
+			result = UpdateResult(matched.unwrap_or_default());
+		}
+		statement.finish(&result, target)
+			.map_err(mysql_async::Error::Other)?;
+		Ok(result)
+	}
+
+	/// inserts `values` into the table of `E`, reporting a duplicate entry as a `DuplicateError`.
+	/// A tenant scoped entity must be inserted through a `TenantQueryObject`.
+	pub async fn insert_entity<E>(&mut self, values: &SqlValues<'_>) -> Result<InsertResult>
+	where E: Table {
+		self.insert_entity_in_scope::<E>(values, None).await
+	}
+
+	pub(super) async fn insert_entity_in_scope<E>(&mut self, values: &SqlValues<'_>, scope: Option<&TenantScope>) -> Result<InsertResult>
+	where E: Table {
+		check_tenant_scope::<E>(scope)
+			.and_then(|_| check_tenant_values::<E>(values))
+			.map_err(|e| mysql_async::Error::Other(Box::new(e)))?;
+		let query = format!("INSERT INTO {} SET {}", E::TABLE_NAME, values.with_named_binding_holder());
+		self.exec_insert(query, values.params()).await
+			.map_err(|e| translate_duplicate(e, |index| Some(E::duplicate(index))))
+	}
+
+	/// inserts the whole `entity`, whose key is known beforehand, like a composite or a generated one, and returns the key.
+	/// The timestamps of the entity that are still NULL are set first.
+	pub async fn insert<E>(&mut self, entity: &mut E) -> Result<E::Key>
+	where E: Table, for<'e> SqlValues<'e>: From<&'e E> {
+		self.insert_in_scope(entity, None).await
+	}
+
+	pub(super) async fn insert_in_scope<E>(&mut self, entity: &mut E, scope: Option<&TenantScope>) -> Result<E::Key>
+	where E: Table, for<'e> SqlValues<'e>: From<&'e E> {
+		entity.touch_inserted();
+		self.insert_entity_in_scope::<E>(&SqlValues::from(&*entity), scope).await?;
+		Ok(entity.get_key())
+	}
+
+	/// inserts `values` into the table of `E`, and returns the key generated by AUTO_INCREMENT
+	pub async fn insert_auto_increment<E>(&mut self, values: &SqlValues<'_>) -> Result<E::Key>
+	where E: Table, E::Key: TryFrom<u64> {
+		self.insert_auto_increment_in_scope::<E>(values, None).await
+	}
+
+	pub(super) async fn insert_auto_increment_in_scope<E>(&mut self, values: &SqlValues<'_>, scope: Option<&TenantScope>) -> Result<E::Key>
+	where E: Table, E::Key: TryFrom<u64> {
+		let id = self.insert_entity_in_scope::<E>(values, scope).await?
+			.insert_id()
+			.map_err(|e| mysql_async::Error::Other(Box::new(e)))?;
+		E::Key::try_from(id).map_err(|_| {
+			let message = format!("the insert id {} does not fit the key of {}", id, E::ENTITY_NAME);
+			mysql_async::Error::Other(Box::new(FromStrError::from(message)))
+		})
+	}
+
+	/// inserts `values` into the table of `E`, or updates the fields other than the keys if the row exists.
+	/// The affected rows are 1 for an insert, 2 for an update, and 0 for a row set to its current values,
+	/// which counts 1 instead if the connection enables `client_found_rows`.
+	pub async fn upsert_entity<E>(&mut self, values: &SqlValues<'_>) -> Result<UpdateResult>
+	where E: Table {
+		self.upsert_entity_in_scope::<E>(values, None).await
+	}
+
+	pub(super) async fn upsert_entity_in_scope<E>(&mut self, values: &SqlValues<'_>, scope: Option<&TenantScope>) -> Result<UpdateResult>
+	where E: Table {
+		check_tenant_scope::<E>(scope)
+			.and_then(|_| check_tenant_values::<E>(values))
+			.map_err(|e| mysql_async::Error::Other(Box::new(e)))?;
+		let query = format!("INSERT INTO {} SET {} ON DUPLICATE KEY UPDATE {}",
+			E::TABLE_NAME, values.with_named_binding_holder(), values.upsert_expressions::<E>());
+		self.exec_update(query, values.params()).await
+			.map_err(|e| translate_duplicate(e, |index| Some(E::duplicate(index))))
+	}
+
+	/// executes `statement` like `exec_update_statement()`, and records the changed columns of `target` to `audit`
+	pub async fn exec_audited_update<E>(&mut self, statement: UpdateStatement<'_, E>, target: &mut E, audit: Audit<'_>) -> Result<UpdateResult>
+	where E: Table {
+		let record = AuditRecord::before(target, statement.updates(), audit.actor());
+		let result = self.exec_update_statement(statement, target).await?;
+		if result.affected_rows() == 0 {
+			return Ok(result);
+		}
+
+		let record = record.after(target);
+		match audit.destination() {
+			AuditDestination::Table(table) => {
+				for row in record.rows() {
+					let query = format!("INSERT INTO {} SET {}", table, row.with_named_binding_holder());
+					self.exec_drop(query, row.params()).await?;
+				}
+			},
+			AuditDestination::Sink(sink) => sink.record(record),
+		}
+		Ok(result)
+	}
+
+	/// deletes the row of `entity`, or only sets its timestamp if the entity has a soft delete column.
+	/// A tenant scoped entity must be deleted through a `TenantQueryObject`.
+	pub async fn delete_entity<E>(&mut self, entity: &mut E) -> Result<UpdateResult>
+	where E: Table, E::Key: Clone + 'static {
+		self.delete_entity_in_scope(entity, None).await
+	}
+
+	pub(super) async fn delete_entity_in_scope<E>(&mut self, entity: &mut E, scope: Option<&TenantScope>) -> Result<UpdateResult>
+	where E: Table, E::Key: Clone + 'static {
+		check_tenant_scope::<E>(scope)
+			.map_err(|e| mysql_async::Error::Other(Box::new(e)))?;
+		if E::SOFT_DELETE_FIELD.is_some() {
+			let statement = scoped_statement(UpdateStatement::soft_delete(entity), scope);
+			return self.exec_update_statement(statement, entity).await;
+		}
+
+		let mut filter = entity.update_filter();
+		if let Some(scope) = scope {
+			filter = scope.filter::<E>(filter);
+		}
+		let query = format!("DELETE FROM {} WHERE {}", E::TABLE_NAME, filter.with_named_binding_holder());
+		let result = self.exec_update(query, filter.params()).await?;
+		if E::VERSION_FIELD.is_some() && result.affected_rows() == 0 {
+			return Err(mysql_async::Error::Other(Box::new(E::lost_update(entity.get_key()))));
+		}
+		Ok(result)
+	}
+
+	/// clears the soft delete timestamp of `entity`
+	pub async fn restore_entity<E>(&mut self, entity: &mut E) -> Result<UpdateResult>
+	where E: Table, E::Key: Clone + 'static {
+		self.restore_entity_in_scope(entity, None).await
+	}
+
+	pub(super) async fn restore_entity_in_scope<E>(&mut self, entity: &mut E, scope: Option<&TenantScope>) -> Result<UpdateResult>
+	where E: Table, E::Key: Clone + 'static {
+		let statement = scoped_statement(UpdateStatement::restore(entity), scope);
+		self.exec_update_statement(statement, entity).await
+	}
+
+	/// reads the next chunk of `scan`, or `None` once the scan is over.
+	/// A tenant scoped entity must be scanned through a `TenantQueryObject`.
+	pub async fn scan_chunk<E>(&mut self, scan: &mut KeysetScan<'_, E>) -> Result<Option<Vec<E>>>
+	where E: Table + FromRow + Send + 'static {
+		self.scan_chunk_in_scope(scan, None).await
+	}
+
+	pub(super) async fn scan_chunk_in_scope<E>(&mut self, scan: &mut KeysetScan<'_, E>, scope: Option<&TenantScope>) -> Result<Option<Vec<E>>>
+	where E: Table + FromRow + Send + 'static {
+		check_tenant_scope::<E>(scope)
+			.map_err(|e| mysql_async::Error::Other(Box::new(e)))?;
+		if scan.is_done() {
+			return Ok(None);
+		}
+
+		let mut filter = scan.chunk_filter();
+		if let Some(scope) = scope {
+			filter = scope.filter::<E>(filter);
+		}
+		let query = scan.statement(filter.with_named_binding_holder());
+		let chunk: Vec<E> = self.exec(query, filter.params()).await?;
+		scan.advance(&chunk)
+			.map_err(|e| mysql_async::Error::Other(Box::new(e)))?;
+		Ok(Some(chunk).filter(|chunk| !chunk.is_empty()))
+	}
+
+	/// loads the children of `parents` with a single `IN (...)` query, grouped by the relation key.
+	/// Tenant scoped children must be loaded through a `TenantQueryObject`.
+	pub async fn load_related<P, C, K>(&mut self, relation: &Relation<P, C, K>, parents: &[P]) -> Result<HashMap<K, Vec<C>>>
+	where
+		C: Table + FromRow + Send + 'static,
+		K: Into<RepoValue<'static>> + Eq + Hash + Clone,
+	{
+		self.load_related_in_scope(relation, parents, None).await
+	}
+
+	pub(super) async fn load_related_in_scope<P, C, K>(&mut self, relation: &Relation<P, C, K>, parents: &[P], scope: Option<&TenantScope>) -> Result<HashMap<K, Vec<C>>>
+	where
+		C: Table + FromRow + Send + 'static,
+		K: Into<RepoValue<'static>> + Eq + Hash + Clone,
+	{
+		let keys = relation.keys(parents);
+		let mut filter = relation.filter(&keys);
+		if let Some(field) = C::SOFT_DELETE_FIELD {
+			filter = filter.with(field, &Filter::<RepoValue>::IsNull);
+		}
+		if let Some(scope) = scope {
+			filter = scope.filter::<C>(filter);
+		}
+		check_tenant_scope::<C>(scope)
+			.and_then(|_| check_tenant_filter::<C>(&filter))
+			.map_err(|e| mysql_async::Error::Other(Box::new(e)))?;
+		if keys.is_empty() {
+			return Ok(HashMap::new());
+		}
+
+		let query = format!("SELECT {} FROM {} WHERE {}", C::TABLE_FIELDS, C::TABLE_NAME, filter.with_named_binding_holder());
+		let children = self.exec(query, filter.params()).await?;
+		Ok(relation.group(children))
+	}
+}
+
+/// restricts `statement` to the rows of the tenant of `scope`, if any
+fn scoped_statement<'a, E: Table>(statement: UpdateStatement<'a, E>, scope: Option<&TenantScope>) -> UpdateStatement<'a, E> {
+	match scope {
+		Some(scope) => scope.statement(statement),
+		None => statement,
+	}
+}
+
+/// turns a duplicate entry reported by the server into the `DuplicateError` built by `duplicate`, if any
+fn translate_duplicate<F>(e: mysql_async::Error, duplicate: F) -> mysql_async::Error
+where F: FnOnce(Option<&str>) -> Option<DuplicateError> {
+	match e {
+		mysql_async::Error::Server(ref server) if is_duplicate_entry_code(server.code) => {
+			match duplicate(duplicate_key_index(&server.message).as_deref()) {
+				Some(duplicate) => mysql_async::Error::Other(Box::new(duplicate)),
+				None => e,
+			}
+		},
+		e => e,
+	}
 }
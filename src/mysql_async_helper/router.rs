@@ -0,0 +1,89 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use mysql_async::Result;
+
+use crate::{Route, Router, ReplicaSelection};
+use super::{ConnectionPool, QueryObject, TransactionManager};
+
+type BoxFuture<'a, T> = futures_core::future::BoxFuture<'a, Result<T>>;
+
+/// Hands out `QueryObject`s of the primary for writes, and of the replicas for reads.
+/// Transactions always run on the primary, so that the reads in a transaction see its writes.
+#[derive(Clone)]
+pub struct ReadWriteRouter {
+	primary: ConnectionPool,
+	replicas: Vec<ConnectionPool>,
+	router: Arc<Router>,
+}
+
+impl ReadWriteRouter {
+	pub fn new(primary: ConnectionPool, replicas: Vec<ConnectionPool>) -> Self {
+		let router = Arc::new(Router::new(replicas.len()));
+		Self { primary, replicas, router }
+	}
+
+	pub fn with_selection(mut self, selection: ReplicaSelection) -> Self {
+		self.router = Arc::new(self.rebuild_router(selection, self.router.sticky_window()));
+		self
+	}
+
+	/// reads go to the primary during `window` after every write, to read your writes;
+	/// the window is shared by all the clones of the router, so a write sends the reads of every caller to the primary
+	pub fn with_sticky_window(mut self, window: Duration) -> Self {
+		self.router = Arc::new(self.rebuild_router(self.router.selection(), Some(window)));
+		self
+	}
+
+	pub fn primary(&self) -> &ConnectionPool {
+		&self.primary
+	}
+
+	pub fn replicas(&self) -> &[ConnectionPool] {
+		&self.replicas
+	}
+
+	/// checks out a connection for reads, of a replica unless there is none or a write is too recent
+	pub async fn read(&self) -> Result<QueryObject<'static>> {
+		let route = self.router.read(|i| {
+			let metrics = self.replicas[i].metrics();
+			metrics.connections.saturating_sub(metrics.idle_connections)
+		});
+		match route {
+			Route::Primary => self.primary.get().await,
+			Route::Replica(i) => self.replicas[i].get().await,
+		}
+	}
+
+	/// checks out a connection of the primary for writes, which starts the sticky window once checked out
+	pub async fn write(&self) -> Result<QueryObject<'static>> {
+		let conn = self.primary.get().await?;
+		self.router.write();
+		Ok(conn)
+	}
+
+	/// returns a transaction manager of the primary, whose transactions are checked out like the writes;
+	/// unlike `transaction()`, its transactions do not start the sticky window
+	pub fn transaction_manager(&self) -> TransactionManager {
+		TransactionManager::new(self.primary.clone())
+	}
+
+	/// runs `f` in a transaction of the primary like `TransactionManager::transaction()`, and then starts the sticky window
+	/// unless the transaction failed, since its writes were rolled back
+	pub async fn transaction<T, F>(&self, f: F) -> Result<T>
+	where F: for<'t, 'q> FnOnce(&'t mut QueryObject<'q>) -> BoxFuture<'t, T> {
+		let result = self.transaction_manager().transaction(f).await;
+		if result.is_ok() {
+			self.router.write();
+		}
+		result
+	}
+
+	fn rebuild_router(&self, selection: ReplicaSelection, sticky_window: Option<Duration>) -> Router {
+		let router = Router::new(self.replicas.len()).with_selection(selection);
+		match sticky_window {
+			Some(window) => router.with_sticky_window(window),
+			None => router,
+		}
+	}
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use mysql_async::{BinaryProtocol, Result, ResultSetStream};
+use mysql_common::prelude::FromRow;
+
+use crate::{Audit, InsertResult, KeysetScan, Relation, RepoValue, SqlFilter, SqlValues, Table, TenantScope, TenantScopeError, UpdateResult, UpdateStatement, check_tenant_updates};
+use super::{MySqlHelper, QueryObject};
+
+/// A `QueryObject` restricted to the rows of a tenant: the tenant is added to the filters, the inserted values
+/// and the updates of the tenant scoped entities, and the entities of other tenants are rejected.
+/// Only the queries it can scope are offered, so that raw SQL cannot bypass the tenant.
+pub struct TenantQueryObject<'s, 'a> {
+	query_object: &'s mut QueryObject<'a>,
+	scope: TenantScope,
+}
+
+impl<'a> QueryObject<'a> {
+	pub fn with_tenant(&mut self, scope: TenantScope) -> TenantQueryObject<'_, 'a> {
+		TenantQueryObject { query_object: self, scope }
+	}
+}
+
+impl TenantQueryObject<'_, '_> {
+	pub fn scope(&self) -> &TenantScope {
+		&self.scope
+	}
+
+	/// adds the tenant to `filter` if `E` is tenant scoped
+	pub fn filter<'f, E: Table>(&self, filter: SqlFilter<'f>) -> SqlFilter<'f> {
+		self.scope.filter::<E>(filter)
+	}
+
+	/// selects the rows of `E` matching `filter` within the tenant
+	pub async fn select<E>(&mut self, filter: SqlFilter<'_>) -> Result<Vec<E>>
+	where E: Table + FromRow + Send + 'static {
+		let filter = self.scope.filter::<E>(filter);
+		self.query_object.exec(select_query::<E>(&filter), filter.params()).await
+	}
+
+	/// streams the rows of `E` matching `filter` within the tenant, which holds the connection until the stream is dropped
+	pub async fn stream_entities<E>(&mut self, filter: SqlFilter<'_>) -> Result<ResultSetStream<'_, '_, 'static, E, BinaryProtocol>>
+	where E: Table + FromRow + Send + Unpin + 'static {
+		let filter = self.scope.filter::<E>(filter);
+		self.query_object.exec_stream(select_query::<E>(&filter), filter.params()).await
+	}
+
+	/// reads the next chunk of `scan` within the tenant, or `None` once the scan is over
+	pub async fn scan_chunk<E>(&mut self, scan: &mut KeysetScan<'_, E>) -> Result<Option<Vec<E>>>
+	where E: Table + FromRow + Send + 'static {
+		self.query_object.scan_chunk_in_scope(scan, Some(&self.scope)).await
+	}
+
+	pub async fn insert_entity<E>(&mut self, values: SqlValues<'_>) -> Result<InsertResult>
+	where E: Table {
+		let values = self.scope.values::<E>(values).map_err(to_error)?;
+		self.query_object.insert_entity_in_scope::<E>(&values, Some(&self.scope)).await
+	}
+
+	pub async fn upsert_entity<E>(&mut self, values: SqlValues<'_>) -> Result<UpdateResult>
+	where E: Table {
+		let values = self.scope.values::<E>(values).map_err(to_error)?;
+		self.query_object.upsert_entity_in_scope::<E>(&values, Some(&self.scope)).await
+	}
+
+	pub async fn insert<E>(&mut self, entity: &mut E) -> Result<E::Key>
+	where E: Table, for<'e> SqlValues<'e>: From<&'e E> {
+		self.scope.check_entity(&*entity).map_err(to_error)?;
+		self.query_object.insert_in_scope(entity, Some(&self.scope)).await
+	}
+
+	pub async fn insert_auto_increment<E>(&mut self, values: SqlValues<'_>) -> Result<E::Key>
+	where E: Table, E::Key: TryFrom<u64> {
+		let values = self.scope.values::<E>(values).map_err(to_error)?;
+		self.query_object.insert_auto_increment_in_scope::<E>(&values, Some(&self.scope)).await
+	}
+
+	pub async fn exec_update_statement<E>(&mut self, statement: UpdateStatement<'_, E>, target: &mut E) -> Result<UpdateResult>
+	where E: Table {
+		self.scope.check_entity(target)
+			.and_then(|_| check_tenant_updates(statement.updates()))
+			.map_err(to_error)?;
+		self.query_object.exec_update_statement(self.scope.statement(statement), target).await
+	}
+
+	pub async fn exec_audited_update<E>(&mut self, statement: UpdateStatement<'_, E>, target: &mut E, audit: Audit<'_>) -> Result<UpdateResult>
+	where E: Table {
+		self.scope.check_entity(target)
+			.and_then(|_| check_tenant_updates(statement.updates()))
+			.map_err(to_error)?;
+		self.query_object.exec_audited_update(self.scope.statement(statement), target, audit).await
+	}
+
+	pub async fn delete_entity<E>(&mut self, entity: &mut E) -> Result<UpdateResult>
+	where E: Table, E::Key: Clone + 'static {
+		self.scope.check_entity(entity).map_err(to_error)?;
+		self.query_object.delete_entity_in_scope(entity, Some(&self.scope)).await
+	}
+
+	pub async fn restore_entity<E>(&mut self, entity: &mut E) -> Result<UpdateResult>
+	where E: Table, E::Key: Clone + 'static {
+		self.scope.check_entity(entity).map_err(to_error)?;
+		self.query_object.restore_entity_in_scope(entity, Some(&self.scope)).await
+	}
+
+	pub async fn load_related<P, C, K>(&mut self, relation: &Relation<P, C, K>, parents: &[P]) -> Result<HashMap<K, Vec<C>>>
+	where
+		C: Table + FromRow + Send + 'static,
+		K: Into<RepoValue<'static>> + Eq + Hash + Clone,
+	{
+		self.query_object.load_related_in_scope(relation, parents, Some(&self.scope)).await
+	}
+}
+
+fn select_query<E: Table>(filter: &SqlFilter<'_>) -> String {
+	if filter.is_empty() {
+		format!("SELECT {} FROM {}", E::TABLE_FIELDS, E::TABLE_NAME)
+	} else {
+		format!("SELECT {} FROM {} WHERE {}", E::TABLE_FIELDS, E::TABLE_NAME, filter.with_named_binding_holder())
+	}
+}
+
+fn to_error(e: TenantScopeError) -> mysql_async::Error {
+	mysql_async::Error::Other(Box::new(e))
+}
@@ -0,0 +1,142 @@
+use mysql_async::{Pool, Result, Transaction, TxOpts};
+use tokio::sync::Mutex;
+
+use crate::{IsolationLevel, TransactionOptions, RetryPolicy, RetriesExhaustedError, is_transient_error_code, is_transient_boxed_error};
+use super::{ConnectionPool, QueryObject};
+
+type BoxFuture<'a, T> = futures_core::future::BoxFuture<'a, Result<T>>;
+
+/// Begins the transactions on the connections of a pool, checked out like with `ConnectionPool::get()`.
+#[derive(Clone)]
+pub struct TransactionManager {
+	pool: ConnectionPool,
+	options: TransactionOptions,
+	retry_policy: RetryPolicy,
+}
+
+impl TransactionManager {
+	/// takes a `ConnectionPool`, or a `Pool` used without its checkout options
+	pub fn new(pool: impl Into<ConnectionPool>) -> Self {
+		Self { pool: pool.into(), options: TransactionOptions::default(), retry_policy: RetryPolicy::default() }
+	}
+
+	pub fn with_options(mut self, options: TransactionOptions) -> Self {
+		self.options = options;
+		self
+	}
+
+	pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+		self.retry_policy = retry_policy;
+		self
+	}
+
+	pub fn pool(&self) -> &Pool {
+		self.pool.pool()
+	}
+
+	pub fn connection_pool(&self) -> &ConnectionPool {
+		&self.pool
+	}
+
+	pub fn options(&self) -> TransactionOptions {
+		self.options
+	}
+
+	pub async fn begin(&self) -> Result<UnitOfWork> {
+		let mut opts = TxOpts::default();
+		opts.with_isolation_level(self.options.isolation_level().map(to_mysql_isolation_level))
+			.with_readonly(self.options.is_read_only().then_some(true));
+		let tx = self.pool.start_transaction(opts).await?;
+		Ok(UnitOfWork { tx: Mutex::new(tx) })
+	}
+
+	/// runs `f` in a new transaction, which is committed if `f` succeeds and rolled back otherwise.
+	/// If `f` panics, the transaction is rolled back when dropped.
+	///
+	/// ```ignore
+	/// let account = manager.transaction(|tx| Box::pin(async move {
+	///     tx.exec_update_statement(statement, &mut account).await?;
+	///     Ok(account)
+	/// })).await?;
+	/// ```
+	pub async fn transaction<T, F>(&self, f: F) -> Result<T>
+	where F: for<'t, 'q> FnOnce(&'t mut QueryObject<'q>) -> BoxFuture<'t, T> {
+		let uow = self.begin().await?;
+		let result = {
+			let mut query_object = uow.query_object().await;
+			f(&mut query_object).await
+		};
+
+		match result {
+			Ok(value) => {
+				uow.commit().await?;
+				Ok(value)
+			},
+			Err(e) => {
+				// the error of `f` matters more than the one of the rollback
+				let _ = uow.rollback().await;
+				Err(e)
+			},
+		}
+	}
+
+	/// runs `f` in a new transaction like `transaction()`, and runs it again in another transaction
+	/// as long as it fails with a transient error, as told by `is_transient_error()`, and the retry policy allows it.
+	/// When no attempt is left, the last error is returned in a `RetriesExhaustedError`.
+	pub async fn transaction_with_retry<T, F>(&self, f: F) -> Result<T>
+	where F: for<'t, 'q> Fn(&'t mut QueryObject<'q>) -> BoxFuture<'t, T> {
+		let mut attempt = 1;
+		loop {
+			match self.transaction(&f).await {
+				Err(e) if is_transient_error(&e) => {
+					if attempt >= self.retry_policy.max_attempts() {
+						return Err(mysql_async::Error::Other(Box::new(RetriesExhaustedError::new(attempt, Box::new(e)))));
+					}
+					tokio::time::sleep(self.retry_policy.backoff(attempt)).await;
+					attempt += 1;
+				},
+				result => return result,
+			}
+		}
+	}
+}
+
+/// returns whether the transaction may succeed if run again after `error`,
+/// like `RepoError::from(error).is_transient()`: a deadlock, a timeout of a lock wait, of a statement or of the pool
+pub fn is_transient_error(error: &mysql_async::Error) -> bool {
+	match error {
+		mysql_async::Error::Server(e) => is_transient_error_code(e.code),
+		mysql_async::Error::Other(e) => is_transient_boxed_error(&**e),
+		_ => false,
+	}
+}
+
+fn to_mysql_isolation_level(level: IsolationLevel) -> mysql_async::IsolationLevel {
+	match level {
+		IsolationLevel::ReadUncommitted => mysql_async::IsolationLevel::ReadUncommitted,
+		IsolationLevel::ReadCommitted => mysql_async::IsolationLevel::ReadCommitted,
+		IsolationLevel::RepeatableRead => mysql_async::IsolationLevel::RepeatableRead,
+		IsolationLevel::Serializable => mysql_async::IsolationLevel::Serializable,
+	}
+}
+
+/// A transaction in progress, which hands out `QueryObject::Tx` handles until it is committed or rolled back.
+/// Dropping it without committing rolls the transaction back.
+pub struct UnitOfWork {
+	tx: Mutex<Transaction<'static>>,
+}
+
+impl UnitOfWork {
+	/// waits for the previous handle, if any, to be dropped
+	pub async fn query_object(&self) -> QueryObject<'_> {
+		QueryObject::Tx(self.tx.lock().await)
+	}
+
+	pub async fn commit(self) -> Result<()> {
+		self.tx.into_inner().commit().await
+	}
+
+	pub async fn rollback(self) -> Result<()> {
+		self.tx.into_inner().rollback().await
+	}
+}
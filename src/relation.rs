@@ -0,0 +1,153 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use crate::{Filter, RepoValue, SqlFilter};
+
+/// Describes how the children of a relation are matched with their parents.
+/// `column` is the column of the child table compared with `IN (...)` against the parent keys.
+pub struct Relation<P, C, K> {
+	column: &'static str,
+	parent_key: fn(&P) -> K,
+	child_key: fn(&C) -> K,
+}
+
+impl<P, C, K> Relation<P, C, K>
+where K: Eq + Hash + Clone {
+	pub fn new(column: &'static str, parent_key: fn(&P) -> K, child_key: fn(&C) -> K) -> Self {
+		Self { column, parent_key, child_key }
+	}
+
+	pub fn column(&self) -> &'static str {
+		self.column
+	}
+
+	/// returns the distinct keys of the parents, in order of first appearance
+	pub fn keys(&self, parents: &[P]) -> Vec<K> {
+		let mut seen = HashSet::new();
+		parents.iter()
+			.map(|p| (self.parent_key)(p))
+			.filter(|k| seen.insert(k.clone()))
+			.collect()
+	}
+
+	pub fn filter(&self, keys: &[K]) -> SqlFilter<'static>
+	where K: Into<RepoValue<'static>> {
+		SqlFilter::default().with(self.column, &Filter::In(keys.to_vec()))
+	}
+
+	pub fn group(&self, children: Vec<C>) -> HashMap<K, Vec<C>> {
+		let mut groups = HashMap::<K, Vec<C>>::new();
+		for child in children {
+			groups.entry((self.child_key)(&child)).or_default().push(child);
+		}
+		groups
+	}
+}
+
+/// returns the key column of the target of a `belongs_to` relation, which is matched against a single field.
+/// Evaluated in a const block by `repo_entity!`, so that a target with a composite key fails to compile.
+///
+/// ```compile_fail,E0080
+/// use repo_helper::repo_entity;
+///
+/// repo_entity!(
+///     #[table_name = "order_lines"]
+///     struct OrderLine {
+///         keys { order_id: u64, line: u32 },
+///         data { product_id: u64 }
+///     }
+/// );
+///
+/// repo_entity!(
+///     #[table_name = "shipments"]
+///     #[belongs_to(line : OrderLine via line_id)]
+///     struct Shipment {
+///         keys { id: u64 },
+///         data { line_id: (u64, u32) }
+///     }
+/// );
+/// ```
+#[doc(hidden)]
+pub const fn single_key_column(key_fields: &'static str) -> &'static str {
+	let bytes = key_fields.as_bytes();
+	let mut i = 0;
+	while i < bytes.len() {
+		assert!(bytes[i] != b',', "the target of a belongs_to relation must have a single key field");
+		i += 1;
+	}
+	key_fields
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{repo_entity, Table};
+	use super::*;
+
+	repo_entity!(
+		#[derive(Debug, Clone, PartialEq)]
+		#[table_name = "customers"]
+		#[has_many(orders : Order via customer_id)]
+		struct Customer {
+			keys { id: u64 },
+			data { name: String }
+		}
+	);
+
+	repo_entity!(
+		#[derive(Debug, Clone, PartialEq)]
+		#[table_name = "orders"]
+		#[belongs_to(customer : Customer via customer_id)]
+		struct Order {
+			keys { id: u64 },
+			data { customer_id: u64 }
+		}
+	);
+
+	fn customer(id: u64) -> Customer {
+		Customer { id, name: format!("customer {}", id) }
+	}
+
+	fn order(id: u64, customer_id: u64) -> Order {
+		Order { id, customer_id }
+	}
+
+	#[test]
+	fn belongs_to_matches_the_key_of_the_target() {
+		let relation = Order::customer();
+		assert_eq!(relation.column(), Customer::KEY_FIELDS);
+
+		let orders = [order(1, 7), order(2, 3), order(3, 7)];
+		let keys = relation.keys(&orders);
+		assert_eq!(keys, vec![7, 3]);
+		assert_eq!(relation.filter(&keys).expressions(), "id IN (7,3)");
+
+		let groups = relation.group(vec![customer(3), customer(7)]);
+		assert_eq!(groups[&7], vec![customer(7)]);
+		assert_eq!(groups[&3], vec![customer(3)]);
+	}
+
+	#[test]
+	fn has_many_matches_the_via_field_of_the_children() {
+		let relation = Customer::orders();
+		assert_eq!(relation.column(), "customer_id");
+
+		let customers = [customer(7), customer(3), customer(7)];
+		let keys = relation.keys(&customers);
+		assert_eq!(relation.filter(&keys).expressions(), "customer_id IN (7,3)");
+
+		let groups = relation.group(vec![order(1, 7), order(2, 3), order(3, 7)]);
+		assert_eq!(groups[&7], vec![order(1, 7), order(3, 7)]);
+		assert_eq!(groups[&3], vec![order(2, 3)]);
+	}
+
+	#[test]
+	fn single_key_column_accepts_one_field() {
+		assert_eq!(single_key_column("id"), "id");
+	}
+
+	#[test]
+	#[should_panic(expected = "single key field")]
+	fn single_key_column_rejects_composite_keys() {
+		single_key_column(std::hint::black_box("tenant_id, id"));
+	}
+}
@@ -42,6 +42,25 @@ impl Display for RepoValue<'_> {
 	}
 }
 
+impl RepoValue<'_> {
+	/// copies the borrowed string, so that the value can outlive its source
+	pub fn into_owned(self) -> RepoValue<'static> {
+		match self {
+			RepoValue::Null => RepoValue::Null,
+			RepoValue::Int(v) => RepoValue::Int(v),
+			RepoValue::UInt(v) => RepoValue::UInt(v),
+			RepoValue::Float(v) => RepoValue::Float(v),
+			RepoValue::Double(v) => RepoValue::Double(v),
+			RepoValue::Date(v) => RepoValue::Date(v),
+			RepoValue::Time(v) => RepoValue::Time(v),
+			RepoValue::DateTime(v) => RepoValue::DateTime(v),
+			RepoValue::Str(v) => RepoValue::String(v.to_string()),
+			RepoValue::String(v) => RepoValue::String(v),
+			RepoValue::Bytes(v) => RepoValue::Bytes(v),
+		}
+	}
+}
+
 impl From<bool> for RepoValue<'_> {
 	fn from(value: bool) -> Self {
 		RepoValue::Int(if value { 1 } else { 0 })
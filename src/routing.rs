@@ -0,0 +1,144 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// How a read picks one of the replicas.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum ReplicaSelection {
+	/// each replica in turn
+	#[default]
+	RoundRobin,
+	/// the replica with the fewest checked out connections
+	LeastConnections,
+}
+
+/// Where a query goes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Route {
+	Primary,
+	/// the index of the replica
+	Replica(usize),
+}
+
+/// Sends writes to the primary and reads to the replicas.
+/// With a sticky window, the reads following a write go to the primary until the window is over,
+/// so that they see the write even if the replicas lag behind.
+/// The window is router-wide rather than per session: a write of any caller sends the reads of every caller
+/// to the primary, which trades some load on the primary for not having to track sessions.
+#[derive(Debug)]
+pub struct Router {
+	replicas: usize,
+	selection: ReplicaSelection,
+	sticky_window: Option<Duration>,
+	next: AtomicUsize,
+	last_write: Mutex<Option<Instant>>,
+}
+
+impl Router {
+	pub fn new(replicas: usize) -> Self {
+		Self {
+			replicas,
+			selection: ReplicaSelection::default(),
+			sticky_window: None,
+			next: AtomicUsize::new(0),
+			last_write: Mutex::new(None),
+		}
+	}
+
+	pub fn with_selection(mut self, selection: ReplicaSelection) -> Self {
+		self.selection = selection;
+		self
+	}
+
+	/// reads go to the primary during `window` after every write, whoever made the write
+	pub fn with_sticky_window(mut self, window: Duration) -> Self {
+		self.sticky_window = Some(window);
+		self
+	}
+
+	pub fn replicas(&self) -> usize {
+		self.replicas
+	}
+
+	pub fn selection(&self) -> ReplicaSelection {
+		self.selection
+	}
+
+	pub fn sticky_window(&self) -> Option<Duration> {
+		self.sticky_window
+	}
+
+	/// routes a read, given the number of connections checked out of each replica
+	pub fn read(&self, in_use: impl Fn(usize) -> usize) -> Route {
+		if self.replicas == 0 || self.is_sticky() {
+			return Route::Primary;
+		}
+
+		match self.selection {
+			ReplicaSelection::RoundRobin => Route::Replica(self.next.fetch_add(1, Ordering::Relaxed) % self.replicas),
+			ReplicaSelection::LeastConnections => {
+				let replica = (0..self.replicas).min_by_key(|&i| in_use(i)).unwrap_or(0);
+				Route::Replica(replica)
+			},
+		}
+	}
+
+	/// routes a write, which starts the sticky window if any;
+	/// callers route the write once it is under way, so that the window is not spent waiting for a connection
+	pub fn write(&self) -> Route {
+		if self.sticky_window.is_some() {
+			*self.last_write.lock().unwrap() = Some(Instant::now());
+		}
+		Route::Primary
+	}
+
+	fn is_sticky(&self) -> bool {
+		let Some(window) = self.sticky_window else {
+			return false;
+		};
+		let last_write = *self.last_write.lock().unwrap();
+		last_write.is_some_and(|at| at.elapsed() < window)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn reads_go_round_robin_over_the_replicas() {
+		let router = Router::new(3);
+		let routes = (0..4).map(|_| router.read(|_| 0)).collect::<Vec<_>>();
+		assert_eq!(routes, [Route::Replica(0), Route::Replica(1), Route::Replica(2), Route::Replica(0)]);
+		assert_eq!(router.write(), Route::Primary);
+	}
+
+	#[test]
+	fn reads_go_to_the_least_busy_replica() {
+		let router = Router::new(3).with_selection(ReplicaSelection::LeastConnections);
+		let in_use = [4, 1, 2];
+		assert_eq!(router.read(|i| in_use[i]), Route::Replica(1));
+	}
+
+	#[test]
+	fn reads_go_to_the_primary_without_replicas() {
+		assert_eq!(Router::new(0).read(|_| 0), Route::Primary);
+	}
+
+	#[test]
+	fn reads_stick_to_the_primary_after_a_write() {
+		let router = Router::new(2).with_sticky_window(Duration::from_millis(30));
+		assert_eq!(router.read(|_| 0), Route::Replica(0));
+		router.write();
+		assert_eq!(router.read(|_| 0), Route::Primary);
+		std::thread::sleep(Duration::from_millis(40));
+		assert_eq!(router.read(|_| 0), Route::Replica(1));
+	}
+
+	#[test]
+	fn reads_do_not_stick_without_a_sticky_window() {
+		let router = Router::new(2);
+		router.write();
+		assert_eq!(router.read(|_| 0), Route::Replica(0));
+	}
+}
@@ -0,0 +1,192 @@
+use std::marker::PhantomData;
+
+use crate::{MissingFieldValueError, RawSql, RepoValue, Table};
+use super::SqlFilter;
+
+/// Reads the rows of `E` matching a filter chunk by chunk, in the order of the keys,
+/// each chunk starting after the last key of the previous one.
+/// Every chunk is a query of its own, so that no transaction spans the whole scan.
+/// `QueryObject::scan_chunk()` and `ExecutorObject::scan_chunk()` run the chunks:
+/// `while let Some(orders) = query_object.scan_chunk(&mut scan).await? { ... }`.
+///
+/// ```
+/// use repo_helper::{repo_entity, KeysetScan, SqlFilter};
+///
+/// repo_entity!(
+///     #[derive(Debug, Clone, PartialEq)]
+///     #[table_name = "orders"]
+///     struct Order {
+///         keys { id: u64 },
+///         data { total: i64 }
+///     }
+/// );
+///
+/// let mut scan = KeysetScan::<Order>::new(SqlFilter::default(), 2);
+/// assert_eq!(scan.statement(scan.chunk_filter().expressions()), "SELECT id, total FROM orders ORDER BY id LIMIT 2");
+///
+/// scan.advance(&[Order { id: 3, total: 10 }, Order { id: 8, total: 5 }])?;
+/// assert_eq!(scan.statement(scan.chunk_filter().expressions()), "SELECT id, total FROM orders WHERE (id > 8) ORDER BY id LIMIT 2");
+///
+/// scan.advance(&[Order { id: 9, total: 1 }])?;
+/// assert!(scan.is_done());
+/// # Ok::<(), repo_helper::MissingFieldValueError>(())
+/// ```
+pub struct KeysetScan<'a, E> {
+	filter: SqlFilter<'a>,
+	chunk_size: usize,
+	/// the values of the key fields of the last row read
+	after: Option<Vec<RepoValue<'static>>>,
+	done: bool,
+	entity: PhantomData<fn() -> E>,
+}
+
+impl<'a, E: Table> KeysetScan<'a, E> {
+	pub fn new(filter: impl Into<SqlFilter<'a>>, chunk_size: usize) -> Self {
+		assert!(chunk_size > 0, "the chunks of a scan must not be empty");
+		Self { filter: filter.into(), chunk_size, after: None, done: false, entity: PhantomData }
+	}
+
+	pub fn chunk_size(&self) -> usize {
+		self.chunk_size
+	}
+
+	/// returns whether the last chunk has been read
+	pub fn is_done(&self) -> bool {
+		self.done
+	}
+
+	/// returns the filter of the next chunk, which also selects the keys after the last row read
+	pub fn chunk_filter(&self) -> SqlFilter<'a> {
+		let mut filter = SqlFilter::default();
+		filter.extend(self.filter.iter().cloned());
+
+		let Some(after) = &self.after else {
+			return filter;
+		};
+		// a raw condition has parameters of its own, which never collide with a condition of the filter on a key
+		let params: Vec<RepoValue<'a>> = after.clone();
+		let condition = match key_fields::<E>().as_slice() {
+			[key] => format!("{} > ?", key),
+			keys => {
+				let placeholders = vec!["?"; keys.len()].join(", ");
+				format!("({}) > ({})", E::KEY_FIELDS, placeholders)
+			},
+		};
+		filter.with_raw_sql(RawSql::trusted(condition, params))
+	}
+
+	/// renders the query of the next chunk from its rendered filter
+	pub fn statement(&self, filter: String) -> String {
+		if filter.is_empty() {
+			format!("SELECT {} FROM {} ORDER BY {} LIMIT {}", E::TABLE_FIELDS, E::TABLE_NAME, E::KEY_FIELDS, self.chunk_size)
+		} else {
+			format!("SELECT {} FROM {} WHERE {} ORDER BY {} LIMIT {}", E::TABLE_FIELDS, E::TABLE_NAME, filter, E::KEY_FIELDS, self.chunk_size)
+		}
+	}
+
+	/// records the last key of the chunk just read; the scan is done once a chunk is not full.
+	/// The values of the key fields are read with `Table::field_value()`, and the scan is left as it was if one is unknown.
+	pub fn advance(&mut self, chunk: &[E]) -> Result<(), MissingFieldValueError> {
+		if let Some(last) = chunk.last() {
+			let after = key_fields::<E>().into_iter()
+				.map(|key| last.field_value(key).ok_or(MissingFieldValueError::new(E::ENTITY_NAME, key)))
+				.collect::<Result<_, _>>()?;
+			self.after = Some(after);
+		}
+		if chunk.len() < self.chunk_size {
+			self.done = true;
+		}
+		Ok(())
+	}
+}
+
+fn key_fields<E: Table>() -> Vec<&'static str> {
+	E::KEY_FIELDS.split(", ").collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{repo_entity, Entity, EntityNotFoundError, Filter, OptimisticLockError};
+	use super::*;
+
+	repo_entity!(
+		#[derive(Debug, Clone, PartialEq)]
+		#[table_name = "orders"]
+		struct Order {
+			keys { id: u64 },
+			data { total: i64 }
+		}
+	);
+
+	repo_entity!(
+		#[derive(Debug, Clone, PartialEq)]
+		#[table_name = "order_lines"]
+		struct OrderLine {
+			keys { order_id: u64, line: u32 },
+			data { quantity: u32 }
+		}
+	);
+
+	/// an entity written by hand, whose `field_value()` knows no field
+	struct Opaque(u64);
+
+	impl Entity for Opaque {
+		type Key = u64;
+
+		fn get_key(&self) -> u64 {
+			self.0
+		}
+
+		fn not_found(key: u64) -> EntityNotFoundError {
+			EntityNotFoundError::new("Opaque", key)
+		}
+	}
+
+	impl Table for Opaque {
+		const TABLE_NAME: &'static str = "opaques";
+		const TABLE_FIELDS: &'static str = "id";
+		const KEY_FIELDS: &'static str = "id";
+
+		fn key_filter(&self) -> SqlFilter<'static> {
+			SqlFilter::default().with("id", &Filter::Equal(self.0))
+		}
+
+		fn lost_update(key: u64) -> OptimisticLockError {
+			OptimisticLockError::new("Opaque", key)
+		}
+	}
+
+	fn next_statement<E: Table>(scan: &KeysetScan<'_, E>) -> String {
+		scan.statement(scan.chunk_filter().expressions())
+	}
+
+	#[test]
+	fn chunks_start_after_the_last_key_read() {
+		let filter = SqlFilter::default().with("id", &Filter::GreaterThan(1u64));
+		let mut scan = KeysetScan::<Order>::new(filter, 2);
+		assert_eq!(next_statement(&scan), "SELECT id, total FROM orders WHERE id>1 ORDER BY id LIMIT 2");
+
+		scan.advance(&[Order { id: 3, total: 1 }, Order { id: 5, total: 2 }]).unwrap();
+		assert!(!scan.is_done());
+		assert_eq!(next_statement(&scan), "SELECT id, total FROM orders WHERE id>1, (id > 5) ORDER BY id LIMIT 2");
+
+		scan.advance(&[]).unwrap();
+		assert!(scan.is_done());
+	}
+
+	#[test]
+	fn composite_keys_are_compared_as_rows() {
+		let mut scan = KeysetScan::<OrderLine>::new(SqlFilter::default(), 10);
+		scan.advance(&[OrderLine { order_id: 4, line: 2, quantity: 1 }]).unwrap();
+		assert!(scan.is_done());
+		assert_eq!(next_statement(&scan), "SELECT order_id, line, quantity FROM order_lines WHERE ((order_id, line) > (4, 2)) ORDER BY order_id, line LIMIT 10");
+	}
+
+	#[test]
+	fn unknown_key_values_leave_the_scan_unchanged() {
+		let mut scan = KeysetScan::<Opaque>::new(SqlFilter::default(), 1);
+		assert_eq!(scan.advance(&[Opaque(1)]), Err(MissingFieldValueError::new("opaques", "id")));
+		assert!(!scan.is_done());
+		assert_eq!(next_statement(&scan), "SELECT id FROM opaques ORDER BY id LIMIT 1");
+	}
+}
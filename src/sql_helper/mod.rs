@@ -1,9 +1,15 @@
 pub use sql_filter::*;
+pub use sql_order::*;
 pub use sql_values::*;
 pub use sql_updates::*;
+pub use sql_update_statement::*;
 pub use sql_result::*;
+pub use keyset_scan::*;
 
 mod sql_filter;
+mod sql_order;
 mod sql_values;
 mod sql_updates;
+mod sql_update_statement;
 mod sql_result;
+mod keyset_scan;
@@ -1,6 +1,6 @@
 use std::fmt::Display;
 
-use crate::{Filter, RepoValue, NamedFilterHolder, NamedFilter};
+use crate::{Filter, RepoValue, NamedFilterHolder, NamedFilter, Subquery, RawSql, FullTextMatch, MatchMode, FromStrError};
 
 #[derive(Default)]
 pub struct SqlFilter<'a>(Vec<NamedFilter<'a>>);
@@ -18,6 +18,36 @@ impl<'a> SqlFilter<'a> {
 		self
 	}
 
+	pub fn with_exists(mut self, subquery: Subquery<RepoValue<'a>>) -> Self {
+		self.0.push(NamedFilter::new(subquery.table(), Filter::Exists(subquery)));
+		self
+	}
+
+	pub fn with_not_exists(mut self, subquery: Subquery<RepoValue<'a>>) -> Self {
+		self.0.push(NamedFilter::new(subquery.table(), Filter::NotExists(subquery)));
+		self
+	}
+
+	/// adds `MATCH(columns) AGAINST (query mode)`; `columns` must be the columns of one FULLTEXT index.
+	/// Fails if there is no column or if a column name is invalid.
+	pub fn with_match<T: Into<RepoValue<'a>>>(mut self, columns: &[&'a str], query: T, mode: MatchMode) -> Result<Self, FromStrError> {
+		let name = columns.first().copied().unwrap_or_default();
+		self.0.push(NamedFilter::new(name, Filter::Match(FullTextMatch::new(columns, query.into(), mode)?)));
+		Ok(self)
+	}
+
+	/// adds a hand-written condition, e.g. `MATCH(title) AGAINST (? IN BOOLEAN MODE)`, with `?` placeholders for `params`.
+	/// Fails if the fragment does not have one placeholder per parameter.
+	pub fn with_raw<T: Into<RepoValue<'a>>>(self, sql_fragment: &str, params: Vec<T>) -> Result<Self, FromStrError> {
+		let params = params.into_iter().map(|v| v.into()).collect();
+		Ok(self.with_raw_sql(RawSql::new(sql_fragment, params)?))
+	}
+
+	pub fn with_raw_sql(mut self, raw: RawSql<RepoValue<'a>>) -> Self {
+		self.0.push(NamedFilter::new("raw", Filter::Raw(raw)));
+		self
+	}
+
 	pub fn is_empty(&self) -> bool {
 		self.0.is_empty()
 	}
@@ -34,8 +64,72 @@ impl<'a> SqlFilter<'a> {
 	}
 }
 
+impl<'a> Extend<NamedFilter<'a>> for SqlFilter<'a> {
+	fn extend<I: IntoIterator<Item = NamedFilter<'a>>>(&mut self, iter: I) {
+		self.0.extend(iter);
+	}
+}
+
+impl<'a> IntoIterator for SqlFilter<'a> {
+	type Item = NamedFilter<'a>;
+	type IntoIter = std::vec::IntoIter<NamedFilter<'a>>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.0.into_iter()
+	}
+}
+
 impl Display for SqlFilter<'_> {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		write!(f, "{}", self.expressions())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use crate::{Comparison, JsonPath};
+	use super::*;
+
+	#[test]
+	fn raw_fragments_render_in_order() {
+		let filter = SqlFilter::default()
+			.with_raw("price * quantity > ?", vec![100]).unwrap()
+			.with_raw("DATEDIFF(shipped_at, ordered_at) > ?", vec![3]).unwrap();
+
+		assert_eq!(filter.expressions(), "(price * quantity > 100), (DATEDIFF(shipped_at, ordered_at) > 3)");
+	}
+
+	#[test]
+	fn raw_fragments_need_one_placeholder_per_param() {
+		assert!(SqlFilter::default().with_raw("price > ? AND quantity > ?", vec![100]).is_err());
+		assert!(SqlFilter::default().with_raw("price > 100", vec![100]).is_err());
+	}
+
+	#[test]
+	fn raw_fragments_ignore_question_marks_in_strings_and_comments() {
+		let sql = "name <> 'why?' AND `odd?` > ? -- really?\n AND /* or? */ price > ? # ok?";
+		let filter = SqlFilter::default().with_raw(sql, vec![1, 2]).unwrap();
+		assert_eq!(filter.expressions(), "(name <> 'why?' AND `odd?` > 1 -- really?\n AND /* or? */ price > 2 # ok?)");
+
+		assert!(SqlFilter::default().with_raw("price --? 1", vec![1]).is_ok());
+	}
+
+	#[test]
+	fn full_text_matches_need_valid_columns() {
+		let filter = SqlFilter::default().with_match(&["title", "body"], "lamp", MatchMode::Boolean).unwrap();
+		assert_eq!(filter.expressions(), "MATCH(title, body) AGAINST ('lamp' IN BOOLEAN MODE)");
+
+		assert!(SqlFilter::default().with_match(&[], "lamp", MatchMode::Boolean).is_err());
+		assert!(SqlFilter::default().with_match(&["title) AGAINST ('x') OR (1"], "lamp", MatchMode::Boolean).is_err());
+	}
+
+	#[test]
+	fn json_filters_render_their_path() {
+		let path = JsonPath::parse("$.tags").unwrap();
+		let filter = SqlFilter::default()
+			.with("data", &Filter::JsonExtract(JsonPath::parse("$.size").unwrap(), Comparison::GreaterThan, 3))
+			.with("data", &Filter::JsonContains("\"red\"", path.clone()))
+			.with("data", &Filter::JsonMemberOf("blue", path));
+		assert_eq!(filter.expressions(), "JSON_EXTRACT(data, '$.size')>3, JSON_CONTAINS(data, '\"red\"', '$.tags'), 'blue' MEMBER OF(JSON_EXTRACT(data, '$.tags'))");
+	}
+}
@@ -0,0 +1,66 @@
+use std::fmt::Display;
+
+use crate::{Filter, FullTextMatch, NamedFilterHolder, RepoValue};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+	Asc,
+	Desc,
+}
+
+impl Direction {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Direction::Asc => "ASC",
+			Direction::Desc => "DESC",
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub enum OrderBy<'a> {
+	Field(&'a str, Direction),
+	Relevance(&'a str, FullTextMatch<RepoValue<'a>>, Direction),
+}
+
+#[derive(Default, Clone)]
+pub struct SqlOrder<'a>(Vec<OrderBy<'a>>);
+
+impl<'a> SqlOrder<'a> {
+	pub fn with(mut self, field: &'a str, direction: Direction) -> Self {
+		self.0.push(OrderBy::Field(field, direction));
+		self
+	}
+
+	/// orders by the relevance of the full-text match held by `filter`; does nothing if it holds no match
+	pub fn with_relevance(mut self, filter: &'a NamedFilterHolder<'a>, direction: Direction) -> Self {
+		if let Some(Filter::Match(m)) = filter.filter() {
+			self.0.push(OrderBy::Relevance(filter.name(), m.clone(), direction));
+		}
+		self
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+
+	pub fn expressions(&self) -> String {
+		self.0.iter()
+			.map(|o| match o {
+				OrderBy::Field(field, direction) => format!("{field} {}", direction.as_str()),
+				OrderBy::Relevance(_, m, direction) => format!("{} {}", m.render(&m.query().to_string()), direction.as_str()),
+			})
+			.collect::<Vec<String>>()
+			.join(", ")
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = &OrderBy<'a>> {
+		self.0.iter()
+	}
+}
+
+impl Display for SqlOrder<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.expressions())
+	}
+}
@@ -1,7 +1,35 @@
-pub struct InsertResult(pub u64);
+use crate::NoInsertIdError;
+
+/// The outcome of an `INSERT`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct InsertResult {
+	last_insert_id: Option<u64>,
+	affected_rows: u64,
+	warnings: u16,
+}
 impl InsertResult {
-	pub fn last_insert_id(&self) -> u64 {
-		self.0
+	pub fn new(last_insert_id: Option<u64>, affected_rows: u64, warnings: u16) -> Self {
+		Self { last_insert_id, affected_rows, warnings }
+	}
+
+	/// the first AUTO_INCREMENT value generated by the statement, `None` if there is none,
+	/// like for a table without AUTO_INCREMENT or an `INSERT IGNORE` which inserted nothing
+	pub fn last_insert_id(&self) -> Option<u64> {
+		self.last_insert_id
+	}
+
+	/// returns the last insert id, or an error if the statement generated none
+	pub fn insert_id(&self) -> Result<u64, NoInsertIdError> {
+		self.last_insert_id.ok_or(NoInsertIdError)
+	}
+
+	pub fn affected_rows(&self) -> u64 {
+		self.affected_rows
+	}
+
+	/// the number of warnings, like the rows skipped by `INSERT IGNORE`; always 0 with sqlx, which does not report them
+	pub fn warnings(&self) -> u16 {
+		self.warnings
 	}
 }
 
@@ -12,3 +40,19 @@ impl UpdateResult {
 	}
 
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn inserts_without_auto_increment_have_no_insert_id() {
+		let ignored = InsertResult::new(None, 0, 1);
+		assert_eq!(ignored.insert_id(), Err(NoInsertIdError));
+		assert_eq!(ignored.warnings(), 1);
+
+		let inserted = InsertResult::new(Some(42), 1, 0);
+		assert_eq!(inserted.insert_id(), Ok(42));
+		assert_eq!(inserted.affected_rows(), 1);
+	}
+}
@@ -0,0 +1,308 @@
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+
+use crate::{DuplicateError, Table, TenantScopeError, UnexpectedAffectedRowsError};
+use super::{SqlFilter, SqlUpdates, UpdateResult};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+type BoxFnConflict<'a> = Box<dyn Fn() -> BoxError + Send + Sync + 'a>;
+type FnDuplicate = fn(Option<&str>) -> DuplicateError;
+
+/// `UPDATE {table} SET {updates} WHERE {filter}`, optionally asserting the number of affected rows.
+/// Without any filter, every row of the table is updated.
+pub struct UpdateStatement<'a, E> {
+	table: &'a str,
+	updates: SqlUpdates<'a, E>,
+	filter: SqlFilter<'a>,
+	expected_rows: Option<RangeInclusive<u64>>,
+	conflict: Option<BoxFnConflict<'a>>,
+	duplicate: Option<FnDuplicate>,
+	/// the entity name and the tenant column of a tenant scoped entity
+	tenant: Option<(&'static str, &'static str)>,
+	/// whether the statement has been restricted by a `TenantScope`
+	scoped: bool,
+}
+
+impl<'a, E> UpdateStatement<'a, E> {
+	pub fn new(table: &'a str, updates: SqlUpdates<'a, E>) -> Self {
+		Self { table, updates, filter: SqlFilter::default(), expected_rows: None, conflict: None, duplicate: None, tenant: None, scoped: false }
+	}
+
+	/// updates the row of `entity`, expecting exactly one row to be matched, so that the updates are not applied to a missing row.
+	/// If the entity has a version, which every update increments, the row must still have the version of `entity`,
+	/// and an `OptimisticLockError` is returned otherwise.
+	/// A row set to its current values is matched but not changed: sqlx always counts the matched rows,
+	/// and the mysql_async backend counts them with `count_statement()` when no row is changed.
+	/// A duplicate entry is reported as a `DuplicateError` naming the fields of the unique key.
+	pub fn for_entity(entity: &E, mut updates: SqlUpdates<'a, E>) -> Self
+	where E: Table, E::Key: Clone + 'static {
+		if !updates.is_empty() {
+			E::touch_updates(&mut updates);
+		}
+		let mut statement = Self::new(E::TABLE_NAME, updates)
+			.with_filter(entity.update_filter())
+			.expect_affected_rows(1);
+		statement.duplicate = Some(E::duplicate);
+		statement.tenant = E::TENANT_FIELD.map(|field| (E::ENTITY_NAME, field));
+
+		match E::VERSION_FIELD {
+			Some(_) => {
+				let key = entity.get_key();
+				statement.on_conflict(move || Box::new(E::lost_update(key.clone())))
+			},
+			None => statement,
+		}
+	}
+
+	/// sets the soft delete timestamp of `entity`; the statement is empty if the entity has no soft delete column
+	pub fn soft_delete(entity: &E) -> Self
+	where E: Table, E::Key: Clone + 'static {
+		let mut updates = SqlUpdates::default();
+		E::mark_deleted(&mut updates);
+		Self::for_entity(entity, updates)
+	}
+
+	/// clears the soft delete timestamp of `entity`; the statement is empty if the entity has no soft delete column
+	pub fn restore(entity: &E) -> Self
+	where E: Table, E::Key: Clone + 'static {
+		let mut updates = SqlUpdates::default();
+		E::mark_restored(&mut updates);
+		Self::for_entity(entity, updates)
+	}
+
+	/// adds the conditions of `filter`, joined with `AND`
+	pub fn with_filter(mut self, filter: SqlFilter<'a>) -> Self {
+		self.filter.extend(filter);
+		self
+	}
+
+	/// replaces the filter with the result of `f`
+	pub fn map_filter<F>(mut self, f: F) -> Self
+	where F: FnOnce(SqlFilter<'a>) -> SqlFilter<'a> {
+		self.filter = f(std::mem::take(&mut self.filter));
+		self
+	}
+
+	/// records that the filter has been restricted to the tenant of a `TenantScope`
+	pub(crate) fn scoped(mut self) -> Self {
+		self.scoped = true;
+		self
+	}
+
+	pub fn expect_affected_rows(self, rows: u64) -> Self {
+		self.expect_affected_rows_in(rows..=rows)
+	}
+
+	pub fn expect_affected_rows_in(mut self, rows: RangeInclusive<u64>) -> Self {
+		self.expected_rows = Some(rows);
+		self
+	}
+
+	/// returns the error built by `f`, instead of `UnexpectedAffectedRowsError`, if no row is affected
+	pub fn on_conflict<F>(mut self, f: F) -> Self
+	where F: Fn() -> BoxError + Send + Sync + 'a {
+		self.conflict = Some(Box::new(f));
+		self
+	}
+
+	pub fn table(&self) -> &'a str {
+		self.table
+	}
+
+	pub fn updates(&self) -> &SqlUpdates<'a, E> {
+		&self.updates
+	}
+
+	pub fn filter(&self) -> &SqlFilter<'a> {
+		&self.filter
+	}
+
+	pub fn expected_rows(&self) -> Option<RangeInclusive<u64>> {
+		self.expected_rows.clone()
+	}
+
+	/// returns the error of a duplicate entry on the unique index `index`, if the statement updates an entity
+	pub fn duplicate(&self, index: Option<&str>) -> Option<DuplicateError> {
+		self.duplicate.map(|duplicate| duplicate(index))
+	}
+
+	/// renders the statement from the already rendered `SET` and `WHERE` clauses
+	pub fn statement(&self, updates: String, filter: String) -> String {
+		if self.filter.is_empty() {
+			format!("UPDATE {} SET {}", self.table, updates)
+		} else {
+			format!("UPDATE {} SET {} WHERE {}", self.table, updates, filter)
+		}
+	}
+
+	/// renders `SELECT COUNT(*)` of the rows matched by the already rendered `WHERE` clause
+	pub fn count_statement(&self, filter: String) -> String {
+		if self.filter.is_empty() {
+			format!("SELECT COUNT(*) FROM {}", self.table)
+		} else {
+			format!("SELECT COUNT(*) FROM {} WHERE {}", self.table, filter)
+		}
+	}
+
+	/// returns whether no row was changed while some are expected, which may only mean that the matched rows were unchanged
+	/// if the driver counts the changed rows, like mysql_async without `client_found_rows`
+	pub fn needs_matched_rows(&self, result: &UpdateResult) -> bool {
+		result.affected_rows() == 0 && self.expected_rows.as_ref().is_some_and(|expected| !expected.contains(&0))
+	}
+
+	pub fn expressions(&self) -> String {
+		let filter = self.filter.iter()
+			.map(|f| f.sql_expression())
+			.collect::<Vec<String>>()
+			.join(" AND ");
+		self.statement(self.updates.expressions(), filter)
+	}
+
+	/// fails if the statement of a tenant scoped entity is not restricted by a `TenantScope`, or assigns the tenant
+	pub fn check_tenant(&self) -> Result<(), TenantScopeError> {
+		match self.tenant {
+			Some((entity, _)) if !self.scoped => Err(TenantScopeError::Missing(entity)),
+			Some((entity, field)) if !self.filter.iter().any(|f| f.name() == field) => Err(TenantScopeError::Missing(entity)),
+			Some((entity, field)) if self.updates.assigns(field) => Err(TenantScopeError::Reassigned(entity)),
+			_ => Ok(()),
+		}
+	}
+
+	pub fn check(&self, result: &UpdateResult) -> Result<(), BoxError> {
+		let affected = result.affected_rows();
+		match (&self.expected_rows, &self.conflict) {
+			(Some(expected), _) if expected.contains(&affected) => Ok(()),
+			(Some(_), Some(conflict)) if affected == 0 => {
+				Err(conflict())
+			},
+			(Some(expected), _) => {
+				let expected = if affected < *expected.start() { *expected.start() } else { *expected.end() };
+				Err(Box::new(UnexpectedAffectedRowsError::new(expected, affected)))
+			},
+			_ => Ok(()),
+		}
+	}
+
+	/// checks the result of the executed statement, and applies the updates to `target` only if it passes
+	pub fn finish(self, result: &UpdateResult, target: &mut E) -> Result<(), BoxError> {
+		self.check(result)?;
+		self.updates.apply(target);
+		Ok(())
+	}
+}
+
+impl<E> Display for UpdateStatement<'_, E> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.expressions())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{repo_entity, Filter, OptimisticLockError, SqlFilter, SqlUpdates, UnexpectedAffectedRowsError, UpdateResult};
+	use super::*;
+
+	repo_entity!(
+		#[derive(Debug, Clone, PartialEq)]
+		#[table_name = "notes"]
+		struct Note {
+			keys { id: u64 },
+			data { text: String }
+		}
+	);
+
+	repo_entity!(
+		#[derive(Debug, Clone, PartialEq)]
+		#[table_name = "accounts"]
+		#[version = version]
+		struct Account {
+			keys { id: u64 },
+			data { balance: i64, version: u32 }
+		}
+	);
+
+	fn set_text(text: &'static str) -> SqlUpdates<'static, Note> {
+		let mut updates = SqlUpdates::default();
+		updates.push("text", text, move |note: &mut Note| note.text = text.to_string());
+		updates
+	}
+
+	fn set_balance(balance: i64) -> SqlUpdates<'static, Account> {
+		let mut updates = SqlUpdates::default();
+		updates.push("balance", balance, move |account: &mut Account| account.balance = balance);
+		updates
+	}
+
+	#[test]
+	fn statement_renders_updates_and_filter() {
+		let statement = UpdateStatement::new("notes", set_text("hello"))
+			.with_filter(SqlFilter::default().with("id", &Filter::GreaterThan(10)));
+		assert_eq!(statement.expressions(), "UPDATE notes SET text='hello' WHERE id>10");
+		assert_eq!(UpdateStatement::new("notes", set_text("hello")).expressions(), "UPDATE notes SET text='hello'");
+	}
+
+	#[test]
+	fn entity_without_version_expects_its_row() {
+		let mut note = Note { id: 1, text: "hi".to_string() };
+		let statement = UpdateStatement::for_entity(&note, set_text("hi"));
+		assert_eq!(statement.expressions(), "UPDATE notes SET text='hi' WHERE id=1");
+		assert_eq!(statement.expected_rows(), Some(1..=1));
+
+		assert!(statement.check(&UpdateResult(1)).is_ok());
+		let error = statement.check(&UpdateResult(2)).unwrap_err();
+		assert_eq!(error.downcast_ref::<UnexpectedAffectedRowsError>(), Some(&UnexpectedAffectedRowsError::new(1, 2)));
+
+		UpdateStatement::for_entity(&note, set_text("hello")).finish(&UpdateResult(1), &mut note).unwrap();
+		assert_eq!(note.text, "hello");
+	}
+
+	#[test]
+	fn missing_row_is_not_updated() {
+		let mut note = Note { id: 1, text: "hi".to_string() };
+		let statement = UpdateStatement::for_entity(&note, set_text("hello"));
+		assert!(statement.needs_matched_rows(&UpdateResult(0)));
+		assert_eq!(statement.count_statement("id=1".to_string()), "SELECT COUNT(*) FROM notes WHERE id=1");
+
+		let error = statement.finish(&UpdateResult(0), &mut note).unwrap_err();
+		assert_eq!(error.downcast_ref::<UnexpectedAffectedRowsError>(), Some(&UnexpectedAffectedRowsError::new(1, 0)));
+		assert_eq!(note.text, "hi");
+	}
+
+	#[test]
+	fn matched_rows_are_only_needed_when_none_changed_but_some_are_expected() {
+		let note = Note { id: 1, text: "hi".to_string() };
+		let statement = UpdateStatement::for_entity(&note, set_text("hello"));
+		assert!(!statement.needs_matched_rows(&UpdateResult(1)));
+		assert!(!UpdateStatement::new("notes", set_text("hi")).needs_matched_rows(&UpdateResult(0)));
+		assert!(!UpdateStatement::new("notes", set_text("hi")).expect_affected_rows_in(0..=5).needs_matched_rows(&UpdateResult(0)));
+	}
+
+	#[test]
+	fn entity_with_version_increments_it_and_reports_lost_updates() {
+		let mut account = Account { id: 7, balance: 10, version: 3 };
+		let statement = UpdateStatement::for_entity(&account, set_balance(20));
+		assert_eq!(statement.expressions(), "UPDATE accounts SET balance=20, version=version+1 WHERE id=7 AND version=3");
+		assert_eq!(statement.expected_rows(), Some(1..=1));
+
+		let error = statement.check(&UpdateResult(0)).unwrap_err();
+		assert_eq!(error.downcast_ref::<OptimisticLockError>(), Some(&OptimisticLockError::new("Account", 7)));
+
+		UpdateStatement::for_entity(&account, set_balance(20)).finish(&UpdateResult(1), &mut account).unwrap();
+		assert_eq!(account, Account { id: 7, balance: 20, version: 4 });
+	}
+
+	#[test]
+	fn finish_leaves_the_target_unchanged_on_failure() {
+		let mut account = Account { id: 7, balance: 10, version: 3 };
+		let before = account.clone();
+		assert!(UpdateStatement::for_entity(&account, set_balance(20)).finish(&UpdateResult(0), &mut account).is_err());
+		assert_eq!(account, before);
+	}
+
+	#[test]
+	fn exact_expectation_reports_the_expected_rows() {
+		let statement = UpdateStatement::new("notes", set_text("hi")).expect_affected_rows(3);
+		let error = statement.check(&UpdateResult(1)).unwrap_err();
+		assert_eq!(error.downcast_ref::<UnexpectedAffectedRowsError>(), Some(&UnexpectedAffectedRowsError::new(3, 1)));
+	}
+}
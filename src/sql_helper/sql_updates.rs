@@ -1,41 +1,232 @@
 use std::fmt::Display;
+use std::ops::{AddAssign, SubAssign};
 
-use crate::RepoValue;
+use crate::{RepoValue, JsonPath};
 use super::SqlValues;
 
 
 type BoxFnApply<'a, E> = Box<dyn FnOnce(&mut E) + Send + Sync + 'a>;
 
-#[derive(Default)]
+/// How a column is assigned in `SET`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Assignment {
+	/// `field = value`
+	Value,
+	/// `field = NULL`
+	Null,
+	/// `field = DEFAULT`
+	Default,
+	/// `field = CURRENT_TIMESTAMP`
+	CurrentTimestamp,
+	/// `field = field + value`
+	Increment,
+	/// `field = field - value`
+	Decrement,
+	/// `field = GREATEST(field, value)`
+	Max,
+	/// `field = LEAST(field, value)`
+	Min,
+	/// `field = COALESCE(field, value)`
+	Coalesce,
+	/// `field = CONCAT(field, value)`
+	Concat,
+	/// `field = JSON_SET(field, path, value)`
+	JsonSet(JsonPath),
+	/// `field = JSON_REMOVE(field, path)`
+	JsonRemove(JsonPath),
+}
+
+impl Assignment {
+	pub fn binds_value(&self) -> bool {
+		!matches!(self, Assignment::Null | Assignment::Default | Assignment::CurrentTimestamp | Assignment::JsonRemove(_))
+	}
+
+	/// `holder` is the text of the bound value, and is ignored if the assignment binds none
+	pub fn expression(&self, field: &str, holder: &str) -> String {
+		match self {
+			Assignment::Value => format!("{field}={holder}"),
+			Assignment::Null => format!("{field}=NULL"),
+			Assignment::Default => format!("{field}=DEFAULT"),
+			Assignment::CurrentTimestamp => format!("{field}=CURRENT_TIMESTAMP"),
+			Assignment::Increment => format!("{field}={field}+{holder}"),
+			Assignment::Decrement => format!("{field}={field}-{holder}"),
+			Assignment::Max => format!("{field}=GREATEST({field}, {holder})"),
+			Assignment::Min => format!("{field}=LEAST({field}, {holder})"),
+			Assignment::Coalesce => format!("{field}=COALESCE({field}, {holder})"),
+			Assignment::Concat => format!("{field}=CONCAT({field}, {holder})"),
+			Assignment::JsonSet(path) => format!("{field}=JSON_SET({field}, {}, {holder})", path.sql_literal()),
+			Assignment::JsonRemove(path) => format!("{field}=JSON_REMOVE({field}, {})", path.sql_literal()),
+		}
+	}
+}
+
+/// `data` holds the bound values in the order of the assignments that bind one.
 pub struct SqlUpdates<'a, E> {
 	data: SqlValues<'a>,
+	assignments: Vec<(&'a str, Assignment)>,
 	appliables: Vec<BoxFnApply<'a, E>>
 }
 
+impl<E> Default for SqlUpdates<'_, E> {
+	fn default() -> Self {
+		Self { data: SqlValues::default(), assignments: Vec::new(), appliables: Vec::new() }
+	}
+}
+
 impl<'a, E> SqlUpdates<'a, E> {
+	/// `field = value`, or `field = NULL` if the value is `RepoValue::Null`
 	pub fn push<T: Into<RepoValue<'a>> + Clone + 'a, F>(&mut self, field: &'a str, data: T, f: F)
 	where F: FnOnce(&mut E) + Send + Sync + 'a {
-		self.data.push(field, data);
+		match data.into() {
+			RepoValue::Null => self.push_assignment(field, Assignment::Null, None, f),
+			data => self.push_assignment(field, Assignment::Value, Some(data), f),
+		}
+	}
+
+	/// `field = DEFAULT`; as the default is only known to the database, `f` can usually do nothing
+	/// and the entity should be reloaded if the new value is needed.
+	pub fn push_default<F>(&mut self, field: &'a str, f: F)
+	where F: FnOnce(&mut E) + Send + Sync + 'a {
+		self.push_assignment(field, Assignment::Default, None, f);
+	}
+
+	/// `field = CURRENT_TIMESTAMP`; like `push_default()`, the new value is only known to the database.
+	pub fn push_current_timestamp<F>(&mut self, field: &'a str, f: F)
+	where F: FnOnce(&mut E) + Send + Sync + 'a {
+		self.push_assignment(field, Assignment::CurrentTimestamp, None, f);
+	}
+
+	/// `field = field + n`; `accessor` returns the field of the entity, which is increased by `n` on `apply()`
+	pub fn increment<T, V, F>(&mut self, field: &'a str, n: T, accessor: F)
+	where
+		T: Into<RepoValue<'a>> + Clone + Send + Sync + 'a,
+		V: AddAssign<T>,
+		F: FnOnce(&mut E) -> &mut V + Send + Sync + 'a,
+	{
+		self.push_assignment(field, Assignment::Increment, Some(n.clone().into()), move |e| *accessor(e) += n);
+	}
+
+	/// `field = field - n`; `accessor` returns the field of the entity, which is decreased by `n` on `apply()`
+	pub fn decrement<T, V, F>(&mut self, field: &'a str, n: T, accessor: F)
+	where
+		T: Into<RepoValue<'a>> + Clone + Send + Sync + 'a,
+		V: SubAssign<T>,
+		F: FnOnce(&mut E) -> &mut V + Send + Sync + 'a,
+	{
+		self.push_assignment(field, Assignment::Decrement, Some(n.clone().into()), move |e| *accessor(e) -= n);
+	}
+
+	/// `field = GREATEST(field, value)`
+	pub fn set_max<T, F>(&mut self, field: &'a str, value: T, accessor: F)
+	where
+		T: Into<RepoValue<'a>> + PartialOrd + Clone + Send + Sync + 'a,
+		F: FnOnce(&mut E) -> &mut T + Send + Sync + 'a,
+	{
+		self.push_assignment(field, Assignment::Max, Some(value.clone().into()), move |e| {
+			let target = accessor(e);
+			if value > *target {
+				*target = value;
+			}
+		});
+	}
+
+	/// `field = LEAST(field, value)`
+	pub fn set_min<T, F>(&mut self, field: &'a str, value: T, accessor: F)
+	where
+		T: Into<RepoValue<'a>> + PartialOrd + Clone + Send + Sync + 'a,
+		F: FnOnce(&mut E) -> &mut T + Send + Sync + 'a,
+	{
+		self.push_assignment(field, Assignment::Min, Some(value.clone().into()), move |e| {
+			let target = accessor(e);
+			if value < *target {
+				*target = value;
+			}
+		});
+	}
+
+	/// `field = COALESCE(field, value)`, which only sets a field that is NULL
+	pub fn coalesce<T, F>(&mut self, field: &'a str, value: T, accessor: F)
+	where
+		T: Into<RepoValue<'a>> + Clone + Send + Sync + 'a,
+		F: FnOnce(&mut E) -> &mut Option<T> + Send + Sync + 'a,
+	{
+		self.push_assignment(field, Assignment::Coalesce, Some(value.clone().into()), move |e| {
+			accessor(e).get_or_insert(value);
+		});
+	}
+
+	/// `field = CONCAT(field, value)`
+	pub fn concat<T, F>(&mut self, field: &'a str, value: T, accessor: F)
+	where
+		T: Into<RepoValue<'a>> + AsRef<str> + Clone + Send + Sync + 'a,
+		F: FnOnce(&mut E) -> &mut String + Send + Sync + 'a,
+	{
+		self.push_assignment(field, Assignment::Concat, Some(value.clone().into()), move |e| {
+			accessor(e).push_str(value.as_ref());
+		});
+	}
+
+	pub fn json_set<T: Into<RepoValue<'a>> + Clone + 'a, F>(&mut self, field: &'a str, path: JsonPath, data: T, f: F)
+	where F: FnOnce(&mut E) + Send + Sync + 'a {
+		self.push_assignment(field, Assignment::JsonSet(path), Some(data.into()), f);
+	}
+
+	pub fn json_remove<F>(&mut self, field: &'a str, path: JsonPath, f: F)
+	where F: FnOnce(&mut E) + Send + Sync + 'a {
+		self.push_assignment(field, Assignment::JsonRemove(path), None, f);
+	}
+
+	fn push_assignment<F>(&mut self, field: &'a str, assignment: Assignment, data: Option<RepoValue<'a>>, f: F)
+	where F: FnOnce(&mut E) + Send + Sync + 'a {
+		if let Some(data) = data {
+			self.data.push(field, data);
+		}
+		self.assignments.push((field, assignment));
 		self.appliables.push(Box::new(f));
 	}
 
 	pub fn is_empty(&self) -> bool {
-		self.data.is_empty()
+		self.assignments.is_empty()
+	}
+
+	/// returns whether `field` is already assigned
+	pub fn assigns(&self, field: &str) -> bool {
+		self.assignments.iter().any(|(f, _)| *f == field)
+	}
+
+	/// renders the assignments, asking `holder` for the text of the i-th assignment's value
+	pub fn render(&self, holder: &mut dyn FnMut(usize, &str) -> String) -> String {
+		self.assignments.iter()
+			.enumerate()
+			.map(|(i, (field, assignment))| {
+				let holder = if assignment.binds_value() { holder(i, field) } else { String::new() };
+				assignment.expression(field, &holder)
+			})
+			.collect::<Vec<String>>()
+			.join(", ")
 	}
 
 	pub fn expressions(&self) -> String {
-		self.data.expressions()
+		let mut values = self.data.iter();
+		self.render(&mut |_, _| {
+			values.next().map(|(_, data)| data.to_string()).unwrap_or_default()
+		})
 	}
 
-	pub fn apply(self, target: &'a mut E) {
+	pub fn apply(self, target: &mut E) {
 		for f in self.appliables.into_iter() {
 			f(target)
 		}
 	}
 
+	/// returns the bound values only, in the order of their holders; `assignments()` tells how each column is assigned
 	pub fn dataset(&self) -> &SqlValues<'a> {
 		&self.data
 	}
+
+	pub fn assignments(&self) -> impl Iterator<Item = &(&'a str, Assignment)> {
+		self.assignments.iter()
+	}
 }
 
 impl<E> Display for SqlUpdates<'_, E> {
@@ -44,8 +235,59 @@ impl<E> Display for SqlUpdates<'_, E> {
 	}
 }
 
-impl<'a, E> AsRef<SqlValues<'a>> for SqlUpdates<'a, E> {
-	fn as_ref(&self) -> &SqlValues<'a> {
-		&self.data
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[derive(Debug, Default, PartialEq)]
+	struct Product {
+		name: String,
+		attributes: String,
+		stock: i64,
+		reserved: i64,
+		max_price: u32,
+		min_price: u32,
+		label: Option<String>,
+	}
+
+	#[test]
+	fn json_updates_bind_only_the_set_values() {
+		let mut updates = SqlUpdates::<Product>::default();
+		updates.push("name", "lamp", |p: &mut Product| p.name = "lamp".to_string());
+		updates.json_set("attributes", JsonPath::parse("$.color").unwrap(), "red", |_: &mut Product| {});
+		updates.json_remove("attributes", JsonPath::parse("$.size").unwrap(), |_: &mut Product| {});
+
+		assert_eq!(updates.expressions(), "name='lamp', attributes=JSON_SET(attributes, '$.color', 'red'), attributes=JSON_REMOVE(attributes, '$.size')");
+		assert_eq!(updates.dataset().iter().map(|(field, _)| *field).collect::<Vec<_>>(), ["name", "attributes"]);
+		assert_eq!(updates.render(&mut |i, _| format!("?{i}")), "name=?0, attributes=JSON_SET(attributes, '$.color', ?1), attributes=JSON_REMOVE(attributes, '$.size')");
+	}
+
+	#[test]
+	fn relative_updates_render_from_the_column_and_apply_to_the_entity() {
+		let mut product = Product { name: "lamp".to_string(), stock: 10, reserved: 4, max_price: 30, min_price: 20, ..Default::default() };
+		let mut updates = SqlUpdates::<Product>::default();
+		updates.increment("stock", 5i64, |p: &mut Product| &mut p.stock);
+		updates.decrement("reserved", 3i64, |p: &mut Product| &mut p.reserved);
+		updates.set_max("max_price", 25u32, |p: &mut Product| &mut p.max_price);
+		updates.set_min("min_price", 15u32, |p: &mut Product| &mut p.min_price);
+		updates.coalesce("label", "new".to_string(), |p: &mut Product| &mut p.label);
+		updates.concat("name", " (blue)".to_string(), |p: &mut Product| &mut p.name);
+
+		assert_eq!(updates.expressions(), "stock=stock+5, reserved=reserved-3, max_price=GREATEST(max_price, 25), min_price=LEAST(min_price, 15), \
+			label=COALESCE(label, 'new'), name=CONCAT(name, ' (blue)')");
+
+		updates.apply(&mut product);
+		assert_eq!((product.stock, product.reserved, product.max_price, product.min_price), (15, 1, 30, 15));
+		assert_eq!(product.label.as_deref(), Some("new"));
+		assert_eq!(product.name, "lamp (blue)");
+	}
+
+	#[test]
+	fn coalesce_keeps_a_value_already_set() {
+		let mut product = Product { label: Some("old".to_string()), ..Default::default() };
+		let mut updates = SqlUpdates::<Product>::default();
+		updates.coalesce("label", "new".to_string(), |p: &mut Product| &mut p.label);
+		updates.apply(&mut product);
+		assert_eq!(product.label.as_deref(), Some("old"));
 	}
 }
@@ -1,6 +1,6 @@
 use std::fmt::Display;
 
-use crate::RepoValue;
+use crate::{RepoValue, Table};
 
 type Pair<'a> = (&'a str, RepoValue<'a>);
 
@@ -17,6 +17,23 @@ impl<'a> SqlValues<'a> {
 		self.0.push((field, data.into()));
 	}
 
+	/// sets the value of `field`, replacing the one already pushed if any
+	pub fn set<T: Into<RepoValue<'a>> + Clone>(&mut self, field: &'a str, data: T) {
+		match self.0.iter_mut().find(|(f, _)| *f == field) {
+			Some((_, value)) => *value = data.into(),
+			None => self.0.push((field, data.into())),
+		}
+	}
+
+	/// sets the value of `field` if it is missing or NULL
+	pub fn set_if_null<T: Into<RepoValue<'a>> + Clone>(&mut self, field: &'a str, data: T) {
+		match self.0.iter_mut().find(|(f, _)| *f == field) {
+			Some((_, value)) if *value == RepoValue::Null => *value = data.into(),
+			Some(_) => {},
+			None => self.0.push((field, data.into())),
+		}
+	}
+
 	pub fn is_empty(&self) -> bool {
 		self.0.is_empty()
 	}
@@ -30,6 +47,29 @@ impl<'a> SqlValues<'a> {
 			.join(", ")
 	}
 
+	/// returns the assignments of `ON DUPLICATE KEY UPDATE`, which set the fields of `E` to the inserted values,
+	/// except the keys, the tenant, the creation timestamp and the soft delete timestamp, which an update never changes.
+	/// The version, if any, is incremented instead.
+	pub fn upsert_expressions<E: Table>(&self) -> String {
+		let keys = E::KEY_FIELDS.split(", ").collect::<Vec<&str>>();
+		let kept = [E::TENANT_FIELD, E::CREATED_AT_FIELD, E::SOFT_DELETE_FIELD];
+		let mut assignments = self.0.iter()
+			.filter(|(field, _)| !keys.contains(field) && !kept.contains(&Some(*field)))
+			.map(|(field, _)| match E::VERSION_FIELD {
+				Some(version) if version == *field => format!("{field}={field}+1"),
+				_ => format!("{field}=VALUES({field})"),
+			})
+			.collect::<Vec<String>>();
+		if let Some(version) = E::VERSION_FIELD.filter(|version| !self.0.iter().any(|(field, _)| field == version)) {
+			assignments.push(format!("{version}={version}+1"));
+		}
+		// a row of keys only is left as it is
+		if let (true, Some(key)) = (assignments.is_empty(), keys.first()) {
+			assignments.push(format!("{key}={key}"));
+		}
+		assignments.join(", ")
+	}
+
 	pub fn iter(&self) -> impl Iterator<Item = &Pair<'a>> {
 		self.0.iter()
 	}
@@ -46,3 +86,81 @@ impl<'a> AsRef<SqlValues<'a>> for SqlValues<'a> {
 		self
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use chrono::NaiveDateTime;
+
+	use crate::repo_entity;
+	use super::*;
+
+	repo_entity!(
+		#[derive(Debug, Clone, PartialEq)]
+		#[table_name = "invoices"]
+		#[version = version]
+		#[soft_delete = deleted_at]
+		#[tenant = tenant_id]
+		#[created_at = created_at]
+		#[updated_at = updated_at]
+		struct Invoice {
+			keys { id: u64 },
+			data {
+				tenant_id: u64,
+				number: String,
+				total: i64,
+				version: u32,
+				created_at: Option<NaiveDateTime>,
+				updated_at: Option<NaiveDateTime>,
+				deleted_at: Option<NaiveDateTime>,
+			}
+		}
+	);
+
+	repo_entity!(
+		#[table_name = "tags"]
+		struct Tag {
+			keys { post_id: u64, name: String },
+			data { color: String }
+		}
+	);
+
+	fn invoice() -> Invoice {
+		Invoice { id: 1, tenant_id: 9, number: "F-1".to_string(), total: 100, version: 2, created_at: None, updated_at: None, deleted_at: None }
+	}
+
+	#[test]
+	fn values_render_as_assignments() {
+		let values = SqlValues::default().with("number", "F-1").with("total", 100);
+		assert_eq!(values.expressions(), "number='F-1', total=100");
+	}
+
+	#[test]
+	fn set_if_null_keeps_the_values_already_set() {
+		let mut values = SqlValues::default().with("a", 1).with("b", RepoValue::Null);
+		values.set_if_null("a", 2);
+		values.set_if_null("b", 3);
+		values.set_if_null("c", 4);
+		assert_eq!(values.expressions(), "a=1, b=3, c=4");
+	}
+
+	#[test]
+	fn upsert_updates_only_the_mutable_fields_and_increments_the_version() {
+		let invoice = invoice();
+		assert_eq!(
+			SqlValues::from(&invoice).upsert_expressions::<Invoice>(),
+			"number=VALUES(number), total=VALUES(total), version=version+1, updated_at=VALUES(updated_at)",
+		);
+	}
+
+	#[test]
+	fn upsert_increments_a_version_missing_from_the_values() {
+		let values = SqlValues::default().with("id", 1).with("tenant_id", 9).with("total", 5);
+		assert_eq!(values.upsert_expressions::<Invoice>(), "total=VALUES(total), version=version+1");
+	}
+
+	#[test]
+	fn upsert_of_keys_only_leaves_the_row_as_it_is() {
+		let values = SqlValues::default().with("post_id", 1).with("name", "rust");
+		assert_eq!(values.upsert_expressions::<Tag>(), "post_id=post_id");
+	}
+}
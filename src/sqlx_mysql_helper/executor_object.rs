@@ -1,13 +1,20 @@
-use sqlx::mysql::MySqlQueryResult;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use sqlx::mysql::{MySqlDatabaseError, MySqlQueryResult, MySqlRow};
 use tokio::sync::MutexGuard;
 use futures_core::future::BoxFuture;
+use futures_core::stream::BoxStream;
 
-use crate::{InsertResult, UpdateResult};
+use crate::{InsertResult, UpdateResult, Relation, RepoValue, Table, UpdateStatement, Filter, Audit, AuditDestination, AuditRecord, Savepoint, NoTransactionError, SqlValues, DuplicateError, FromStrError, TenantScope, KeysetScan, SqlFilter, is_duplicate_entry_code, check_tenant_filter, check_tenant_scope, check_tenant_values};
+use crate::errors::duplicate_key_index;
+use super::{BindFilter, BindUpdateStatement, BindValues, SqlxHelper};
 
 type SqlxConn = sqlx::pool::PoolConnection<sqlx::MySql>;
 type SqlxTransaction<'t> = sqlx::Transaction<'t, sqlx::MySql>;
 
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum ExecutorObject<'a> {
 	Conn(SqlxConn),
 	MutexGuardTransaction(MutexGuard<'a, SqlxTransaction<'static>>)
@@ -16,7 +23,7 @@ pub enum ExecutorObject<'a> {
 impl<'c> sqlx::Executor<'c> for &'c mut ExecutorObject<'_> {
 	type Database = sqlx::MySql;
 
-	fn fetch_many<'e, 'q: 'e, E: 'q>(
+	fn fetch_many<'e, 'q: 'e, E>(
 		self,
 		query: E,
 	) -> futures_core::stream::BoxStream<
@@ -28,20 +35,20 @@ impl<'c> sqlx::Executor<'c> for &'c mut ExecutorObject<'_> {
 	>
 	where
 		'c: 'e,
-		E: sqlx::Execute<'q, Self::Database> {
+		E: 'q + sqlx::Execute<'q, Self::Database> {
 		match self {
 			ExecutorObject::Conn(conn) => conn.fetch_many(query),
 			ExecutorObject::MutexGuardTransaction(tx) => tx.fetch_many(query)
 		}
 	}
 
-	fn fetch_optional<'e, 'q: 'e, E: 'q>(
+	fn fetch_optional<'e, 'q: 'e, E>(
 		self,
 		query: E,
 	) -> BoxFuture<'e, Result<Option<<Self::Database as sqlx::Database>::Row>, sqlx::Error>>
 	where
 		'c: 'e,
-		E: sqlx::Execute<'q, Self::Database> {
+		E: 'q + sqlx::Execute<'q, Self::Database> {
 		match self {
 			ExecutorObject::Conn(conn) => conn.fetch_optional(query),
 			ExecutorObject::MutexGuardTransaction(tx) => tx.fetch_optional(query)
@@ -74,9 +81,299 @@ impl<'c> sqlx::Executor<'c> for &'c mut ExecutorObject<'_> {
 	}
 }
 
+impl ExecutorObject<'_> {
+	/// runs `f` in a nested transaction: a savepoint released if `f` succeeds, and rolled back to otherwise,
+	/// so that the enclosing transaction goes on if the caller handles the error
+	pub async fn transaction<T, F>(&mut self, f: F) -> Result<T, sqlx::Error>
+	where F: for<'t> FnOnce(&'t mut Self) -> BoxFuture<'t, Result<T, sqlx::Error>> {
+		if let ExecutorObject::Conn(_) = self {
+			return Err(sqlx::Error::Decode(Box::new(NoTransactionError)));
+		}
+
+		let savepoint = Savepoint::new();
+		sqlx::query(&savepoint.create_statement()).execute(&mut *self).await?;
+		match f(self).await {
+			Ok(value) => {
+				sqlx::query(&savepoint.release_statement()).execute(&mut *self).await?;
+				Ok(value)
+			},
+			Err(e) => {
+				// the error of `f` matters more than the one of the rollback
+				let _ = sqlx::query(&savepoint.rollback_statement()).execute(&mut *self).await;
+				Err(e)
+			},
+		}
+	}
+
+	/// streams the rows of `query`, bound to the parameters of `filter`, instead of collecting them,
+	/// which holds the connection until the stream is dropped
+	pub fn fetch_stream<'e, 'q: 'e, T>(&'e mut self, query: &'q str, filter: &'q SqlFilter<'_>) -> BoxStream<'e, Result<T, sqlx::Error>>
+	where T: for<'r> sqlx::FromRow<'r, MySqlRow> + Send + Unpin + 'e {
+		sqlx::query_as::<_, T>(query)
+			.bind_filter(filter)
+			.fetch(self)
+	}
+
+	/// reads the next chunk of `scan`, or `None` once the scan is over.
+	/// A tenant scoped entity must be scanned through a `TenantExecutorObject`.
+	pub async fn scan_chunk<E>(&mut self, scan: &mut KeysetScan<'_, E>) -> Result<Option<Vec<E>>, sqlx::Error>
+	where E: Table + for<'r> sqlx::FromRow<'r, MySqlRow> + Send + Unpin {
+		self.scan_chunk_in_scope(scan, None).await
+	}
+
+	pub(super) async fn scan_chunk_in_scope<E>(&mut self, scan: &mut KeysetScan<'_, E>, scope: Option<&TenantScope>) -> Result<Option<Vec<E>>, sqlx::Error>
+	where E: Table + for<'r> sqlx::FromRow<'r, MySqlRow> + Send + Unpin {
+		check_tenant_scope::<E>(scope)
+			.map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+		if scan.is_done() {
+			return Ok(None);
+		}
+
+		let mut filter = scan.chunk_filter();
+		if let Some(scope) = scope {
+			filter = scope.filter::<E>(filter);
+		}
+		let query = scan.statement(filter.with_binding_holder());
+		let chunk = sqlx::query_as::<_, E>(&query)
+			.bind_filter(&filter)
+			.fetch_all(&mut *self)
+			.await?;
+		scan.advance(&chunk)
+			.map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+		Ok(Some(chunk).filter(|chunk| !chunk.is_empty()))
+	}
+
+	/// executes `statement`, and applies its updates to `target` once the expected number of rows has been affected
+	pub async fn exec_update_statement<E>(&mut self, statement: UpdateStatement<'_, E>, target: &mut E) -> Result<UpdateResult, sqlx::Error> {
+		if statement.updates().is_empty() {
+			return Ok(UpdateResult(0));
+		}
+		statement.check_tenant()
+			.map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+		let query = statement.with_binding_holder();
+		let result = sqlx::query(&query)
+			.bind_update_statement(&statement)
+			.execute(&mut *self)
+			.await
+			.map_err(|e| translate_duplicate(e, |index| statement.duplicate(index)))?;
+		let result = UpdateResult::from(result);
+		statement.finish(&result, target)
+			.map_err(sqlx::Error::Decode)?;
+		Ok(result)
+	}
+
+	/// inserts `values` into the table of `E`, reporting a duplicate entry as a `DuplicateError`.
+	/// A tenant scoped entity must be inserted through a `TenantExecutorObject`.
+	pub async fn insert_entity<E>(&mut self, values: &SqlValues<'_>) -> Result<InsertResult, sqlx::Error>
+	where E: Table {
+		self.insert_entity_in_scope::<E>(values, None).await
+	}
+
+	pub(super) async fn insert_entity_in_scope<E>(&mut self, values: &SqlValues<'_>, scope: Option<&TenantScope>) -> Result<InsertResult, sqlx::Error>
+	where E: Table {
+		check_tenant_scope::<E>(scope)
+			.and_then(|_| check_tenant_values::<E>(values))
+			.map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+		let query = format!("INSERT INTO {} SET {}", E::TABLE_NAME, values.with_binding_holder());
+		let result = sqlx::query(&query)
+			.bind_values(values)
+			.execute(&mut *self)
+			.await
+			.map_err(|e| translate_duplicate(e, |index| Some(E::duplicate(index))))?;
+		Ok(InsertResult::from(result))
+	}
+
+	/// inserts the whole `entity`, whose key is known beforehand, like a composite or a generated one, and returns the key.
+	/// The timestamps of the entity that are still NULL are set first.
+	pub async fn insert<E>(&mut self, entity: &mut E) -> Result<E::Key, sqlx::Error>
+	where E: Table, for<'e> SqlValues<'e>: From<&'e E> {
+		self.insert_in_scope(entity, None).await
+	}
+
+	pub(super) async fn insert_in_scope<E>(&mut self, entity: &mut E, scope: Option<&TenantScope>) -> Result<E::Key, sqlx::Error>
+	where E: Table, for<'e> SqlValues<'e>: From<&'e E> {
+		entity.touch_inserted();
+		self.insert_entity_in_scope::<E>(&SqlValues::from(&*entity), scope).await?;
+		Ok(entity.get_key())
+	}
+
+	/// inserts `values` into the table of `E`, and returns the key generated by AUTO_INCREMENT
+	pub async fn insert_auto_increment<E>(&mut self, values: &SqlValues<'_>) -> Result<E::Key, sqlx::Error>
+	where E: Table, E::Key: TryFrom<u64> {
+		self.insert_auto_increment_in_scope::<E>(values, None).await
+	}
+
+	pub(super) async fn insert_auto_increment_in_scope<E>(&mut self, values: &SqlValues<'_>, scope: Option<&TenantScope>) -> Result<E::Key, sqlx::Error>
+	where E: Table, E::Key: TryFrom<u64> {
+		let id = self.insert_entity_in_scope::<E>(values, scope).await?
+			.insert_id()
+			.map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+		E::Key::try_from(id).map_err(|_| {
+			let message = format!("the insert id {} does not fit the key of {}", id, E::ENTITY_NAME);
+			sqlx::Error::Decode(Box::new(FromStrError::from(message)))
+		})
+	}
+
+	/// inserts `values` into the table of `E`, or updates the fields other than the keys if the row exists.
+	/// The affected rows are 1 for an insert or a row set to its current values, and 2 for an update,
+	/// since sqlx always enables `CLIENT_FOUND_ROWS`.
+	pub async fn upsert_entity<E>(&mut self, values: &SqlValues<'_>) -> Result<UpdateResult, sqlx::Error>
+	where E: Table {
+		self.upsert_entity_in_scope::<E>(values, None).await
+	}
+
+	pub(super) async fn upsert_entity_in_scope<E>(&mut self, values: &SqlValues<'_>, scope: Option<&TenantScope>) -> Result<UpdateResult, sqlx::Error>
+	where E: Table {
+		check_tenant_scope::<E>(scope)
+			.and_then(|_| check_tenant_values::<E>(values))
+			.map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+		let query = format!("INSERT INTO {} SET {} ON DUPLICATE KEY UPDATE {}",
+			E::TABLE_NAME, values.with_binding_holder(), values.upsert_expressions::<E>());
+		let result = sqlx::query(&query)
+			.bind_values(values)
+			.execute(&mut *self)
+			.await
+			.map_err(|e| translate_duplicate(e, |index| Some(E::duplicate(index))))?;
+		Ok(UpdateResult::from(result))
+	}
+
+	/// executes `statement` like `exec_update_statement()`, and records the changed columns of `target` to `audit`
+	pub async fn exec_audited_update<E>(&mut self, statement: UpdateStatement<'_, E>, target: &mut E, audit: Audit<'_>) -> Result<UpdateResult, sqlx::Error>
+	where E: Table {
+		let record = AuditRecord::before(target, statement.updates(), audit.actor());
+		let result = self.exec_update_statement(statement, target).await?;
+		if result.affected_rows() == 0 {
+			return Ok(result);
+		}
+
+		let record = record.after(target);
+		match audit.destination() {
+			AuditDestination::Table(table) => {
+				for row in record.rows() {
+					let query = format!("INSERT INTO {} SET {}", table, row.with_binding_holder());
+					sqlx::query(&query)
+						.bind_values(&row)
+						.execute(&mut *self)
+						.await?;
+				}
+			},
+			AuditDestination::Sink(sink) => sink.record(record),
+		}
+		Ok(result)
+	}
+
+	/// deletes the row of `entity`, or only sets its timestamp if the entity has a soft delete column.
+	/// A tenant scoped entity must be deleted through a `TenantExecutorObject`.
+	pub async fn delete_entity<E>(&mut self, entity: &mut E) -> Result<UpdateResult, sqlx::Error>
+	where E: Table, E::Key: Clone + 'static {
+		self.delete_entity_in_scope(entity, None).await
+	}
+
+	pub(super) async fn delete_entity_in_scope<E>(&mut self, entity: &mut E, scope: Option<&TenantScope>) -> Result<UpdateResult, sqlx::Error>
+	where E: Table, E::Key: Clone + 'static {
+		check_tenant_scope::<E>(scope)
+			.map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+		if E::SOFT_DELETE_FIELD.is_some() {
+			let statement = scoped_statement(UpdateStatement::soft_delete(entity), scope);
+			return self.exec_update_statement(statement, entity).await;
+		}
+
+		let mut filter = entity.update_filter();
+		if let Some(scope) = scope {
+			filter = scope.filter::<E>(filter);
+		}
+		let query = format!("DELETE FROM {} WHERE {}", E::TABLE_NAME, filter.with_binding_holder());
+		let result = sqlx::query(&query)
+			.bind_filter(&filter)
+			.execute(&mut *self)
+			.await?;
+		let result = UpdateResult::from(result);
+		if E::VERSION_FIELD.is_some() && result.affected_rows() == 0 {
+			return Err(sqlx::Error::Decode(Box::new(E::lost_update(entity.get_key()))));
+		}
+		Ok(result)
+	}
+
+	/// clears the soft delete timestamp of `entity`
+	pub async fn restore_entity<E>(&mut self, entity: &mut E) -> Result<UpdateResult, sqlx::Error>
+	where E: Table, E::Key: Clone + 'static {
+		self.restore_entity_in_scope(entity, None).await
+	}
+
+	pub(super) async fn restore_entity_in_scope<E>(&mut self, entity: &mut E, scope: Option<&TenantScope>) -> Result<UpdateResult, sqlx::Error>
+	where E: Table, E::Key: Clone + 'static {
+		let statement = scoped_statement(UpdateStatement::restore(entity), scope);
+		self.exec_update_statement(statement, entity).await
+	}
+
+	/// loads the children of `parents` with a single `IN (...)` query, grouped by the relation key.
+	/// Tenant scoped children must be loaded through a `TenantExecutorObject`.
+	pub async fn load_related<P, C, K>(&mut self, relation: &Relation<P, C, K>, parents: &[P]) -> Result<HashMap<K, Vec<C>>, sqlx::Error>
+	where
+		C: Table + for<'r> sqlx::FromRow<'r, MySqlRow> + Send + Unpin,
+		K: Into<RepoValue<'static>> + Eq + Hash + Clone,
+	{
+		self.load_related_in_scope(relation, parents, None).await
+	}
+
+	pub(super) async fn load_related_in_scope<P, C, K>(&mut self, relation: &Relation<P, C, K>, parents: &[P], scope: Option<&TenantScope>) -> Result<HashMap<K, Vec<C>>, sqlx::Error>
+	where
+		C: Table + for<'r> sqlx::FromRow<'r, MySqlRow> + Send + Unpin,
+		K: Into<RepoValue<'static>> + Eq + Hash + Clone,
+	{
+		let keys = relation.keys(parents);
+		let mut filter = relation.filter(&keys);
+		if let Some(field) = C::SOFT_DELETE_FIELD {
+			filter = filter.with(field, &Filter::<RepoValue>::IsNull);
+		}
+		if let Some(scope) = scope {
+			filter = scope.filter::<C>(filter);
+		}
+		check_tenant_scope::<C>(scope)
+			.and_then(|_| check_tenant_filter::<C>(&filter))
+			.map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+		if keys.is_empty() {
+			return Ok(HashMap::new());
+		}
+
+		let query = format!("SELECT {} FROM {} WHERE {}", C::TABLE_FIELDS, C::TABLE_NAME, filter.with_binding_holder());
+		let children = sqlx::query_as::<_, C>(&query)
+			.bind_filter(&filter)
+			.fetch_all(&mut *self)
+			.await?;
+		Ok(relation.group(children))
+	}
+}
+
+/// restricts `statement` to the rows of the tenant of `scope`, if any
+fn scoped_statement<'a, E: Table>(statement: UpdateStatement<'a, E>, scope: Option<&TenantScope>) -> UpdateStatement<'a, E> {
+	match scope {
+		Some(scope) => scope.statement(statement),
+		None => statement,
+	}
+}
+
+/// turns a duplicate entry reported by the server into the `DuplicateError` built by `duplicate`, if any
+fn translate_duplicate<F>(e: sqlx::Error, duplicate: F) -> sqlx::Error
+where F: FnOnce(Option<&str>) -> Option<DuplicateError> {
+	let duplicate = match &e {
+		sqlx::Error::Database(db) => db.try_downcast_ref::<MySqlDatabaseError>()
+			.filter(|mysql| is_duplicate_entry_code(mysql.number()))
+			.and_then(|mysql| duplicate(duplicate_key_index(mysql.message()).as_deref())),
+		_ => None,
+	};
+	match duplicate {
+		Some(duplicate) => sqlx::Error::Decode(Box::new(duplicate)),
+		None => e,
+	}
+}
+
 impl From<MySqlQueryResult> for InsertResult {
 	fn from(result: MySqlQueryResult) -> Self {
-		InsertResult(result.last_insert_id())
+		// the server reports 0 when no AUTO_INCREMENT value was generated
+		let last_insert_id = Some(result.last_insert_id()).filter(|id| *id != 0);
+		InsertResult::new(last_insert_id, result.rows_affected(), 0)
 	}
 }
 
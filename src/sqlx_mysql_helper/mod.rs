@@ -1,5 +1,11 @@
 mod executor_object;
+mod router;
+mod tenant_executor_object;
 mod sqlx_helper;
+mod transaction_manager;
 
 pub use executor_object::*;
+pub use router::*;
+pub use tenant_executor_object::*;
 pub use sqlx_helper::*;
+pub use transaction_manager::*;
@@ -0,0 +1,90 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use sqlx::MySqlPool;
+use futures_core::future::BoxFuture;
+
+use crate::{Route, Router, ReplicaSelection};
+use super::{ExecutorObject, TransactionManager};
+
+/// Hands out `ExecutorObject`s of the primary for writes, and of the replicas for reads.
+/// Transactions always run on the primary, so that the reads in a transaction see its writes.
+#[derive(Clone)]
+pub struct ReadWriteRouter {
+	primary: MySqlPool,
+	replicas: Vec<MySqlPool>,
+	router: Arc<Router>,
+}
+
+impl ReadWriteRouter {
+	pub fn new(primary: MySqlPool, replicas: Vec<MySqlPool>) -> Self {
+		let router = Arc::new(Router::new(replicas.len()));
+		Self { primary, replicas, router }
+	}
+
+	pub fn with_selection(mut self, selection: ReplicaSelection) -> Self {
+		self.router = Arc::new(self.rebuild_router(selection, self.router.sticky_window()));
+		self
+	}
+
+	/// reads go to the primary during `window` after every write, to read your writes;
+	/// the window is shared by all the clones of the router, so a write sends the reads of every caller to the primary
+	pub fn with_sticky_window(mut self, window: Duration) -> Self {
+		self.router = Arc::new(self.rebuild_router(self.router.selection(), Some(window)));
+		self
+	}
+
+	pub fn primary(&self) -> &MySqlPool {
+		&self.primary
+	}
+
+	pub fn replicas(&self) -> &[MySqlPool] {
+		&self.replicas
+	}
+
+	/// acquires a connection for reads, of a replica unless there is none or a write is too recent
+	pub async fn read(&self) -> Result<ExecutorObject<'static>, sqlx::Error> {
+		let route = self.router.read(|i| {
+			let pool = &self.replicas[i];
+			(pool.size() as usize).saturating_sub(pool.num_idle())
+		});
+		let pool = match route {
+			Route::Primary => &self.primary,
+			Route::Replica(i) => &self.replicas[i],
+		};
+		Ok(ExecutorObject::Conn(pool.acquire().await?))
+	}
+
+	/// acquires a connection of the primary for writes, which starts the sticky window once acquired
+	pub async fn write(&self) -> Result<ExecutorObject<'static>, sqlx::Error> {
+		let conn = self.primary.acquire().await?;
+		self.router.write();
+		Ok(ExecutorObject::Conn(conn))
+	}
+
+	/// returns a transaction manager of the primary, whose transactions acquire their connection like the writes,
+	/// with the acquire timeout, the health check and the `after_connect` setup of the pool;
+	/// unlike `transaction()`, its transactions do not start the sticky window
+	pub fn transaction_manager(&self) -> TransactionManager {
+		TransactionManager::new(self.primary.clone())
+	}
+
+	/// runs `f` in a transaction of the primary like `TransactionManager::transaction()`, and then starts the sticky window
+	/// unless the transaction failed, since its writes were rolled back
+	pub async fn transaction<T, F>(&self, f: F) -> Result<T, sqlx::Error>
+	where F: for<'t, 'e> FnOnce(&'t mut ExecutorObject<'e>) -> BoxFuture<'t, Result<T, sqlx::Error>> {
+		let result = self.transaction_manager().transaction(f).await;
+		if result.is_ok() {
+			self.router.write();
+		}
+		result
+	}
+
+	fn rebuild_router(&self, selection: ReplicaSelection, sticky_window: Option<Duration>) -> Router {
+		let router = Router::new(self.replicas.len()).with_selection(selection);
+		match sticky_window {
+			Some(window) => router.with_sticky_window(window),
+			None => router,
+		}
+	}
+}
@@ -1,4 +1,4 @@
-use crate::{Filter, SqlFilter, SqlValues, SqlUpdates, RepoValue};
+use crate::{Filter, SqlFilter, SqlValues, SqlUpdates, SqlOrder, OrderBy, RepoValue, Subquery, UpdateStatement};
 
 type SqlxQueryAs<'q, O> = sqlx::query::QueryAs<'q, sqlx::MySql, O, sqlx::mysql::MySqlArguments>;
 type SqlxQuery<'q> = sqlx::query::Query<'q, sqlx::MySql, sqlx::mysql::MySqlArguments>;
@@ -12,32 +12,52 @@ pub trait SqlxHelper {
 impl SqlxHelper for SqlFilter<'_> {
 	fn with_binding_holder(&self) -> String {
 		self.iter()
-			.map(|f| {
-				let field = f.name();
-				match f.filter() {
-					Filter::Equal(_) => format!("{field}={PARAM_SYMBOL}"),
-					Filter::Not(_) => format!("{field}<>{PARAM_SYMBOL}"),
-					Filter::LessorThan(_) => format!("{field}<{PARAM_SYMBOL}"),
-					Filter::EqualOrLessorThan(_) => format!("{field}<={PARAM_SYMBOL}"),
-					Filter::GreaterThan(_) => format!("{field}>{PARAM_SYMBOL}"),
-					Filter::EqualOrGreaterThan(_) => format!("{field}>={PARAM_SYMBOL}"),
-					Filter::In(v) => {
-						let holders = comma_seperated_binding_holders(v.len());
-						format!("{field} IN ({holders})")
-					},
-					Filter::NotIn(v) => {
-						let holders = comma_seperated_binding_holders(v.len());
-						format!("{field} NOT IN ({holders})")
-					},
-					Filter::Between(_, _) => {
-						format!("{field} BETWEEN {PARAM_SYMBOL} AND {PARAM_SYMBOL}")
-					},
-				}
-			})
+			.map(|f| filter_binding_holder(f.name(), f.filter()))
 			.collect::<Vec<String>>().join(" AND ")
 	}
 }
 
+fn filter_binding_holder(field: &str, filter: &Filter<RepoValue<'_>>) -> String {
+	match filter {
+		Filter::Equal(_) => format!("{field}={PARAM_SYMBOL}"),
+		Filter::Not(_) => format!("{field}<>{PARAM_SYMBOL}"),
+		Filter::LessorThan(_) => format!("{field}<{PARAM_SYMBOL}"),
+		Filter::EqualOrLessorThan(_) => format!("{field}<={PARAM_SYMBOL}"),
+		Filter::GreaterThan(_) => format!("{field}>{PARAM_SYMBOL}"),
+		Filter::EqualOrGreaterThan(_) => format!("{field}>={PARAM_SYMBOL}"),
+		Filter::In(v) => {
+			let holders = comma_seperated_binding_holders(v.len());
+			format!("{field} IN ({holders})")
+		},
+		Filter::NotIn(v) => {
+			let holders = comma_seperated_binding_holders(v.len());
+			format!("{field} NOT IN ({holders})")
+		},
+		Filter::Between(_, _) => {
+			format!("{field} BETWEEN {PARAM_SYMBOL} AND {PARAM_SYMBOL}")
+		},
+		Filter::IsNull => format!("{field} IS NULL"),
+		Filter::IsNotNull => format!("{field} IS NOT NULL"),
+		Filter::InSubquery(s) => format!("{field} IN ({})", subquery_binding_holder(s)),
+		Filter::NotInSubquery(s) => format!("{field} NOT IN ({})", subquery_binding_holder(s)),
+		Filter::Exists(s) => format!("EXISTS ({})", subquery_binding_holder(s)),
+		Filter::NotExists(s) => format!("NOT EXISTS ({})", subquery_binding_holder(s)),
+		Filter::Compare(lhs, op, rhs) => op.render(lhs, rhs, &mut |_| PARAM_SYMBOL.to_string()),
+		Filter::Raw(raw) => raw.render(&mut |_| PARAM_SYMBOL.to_string()),
+		Filter::Match(m) => m.render(PARAM_SYMBOL),
+		Filter::JsonExtract(path, op, _) => format!("{}{}{PARAM_SYMBOL}", path.extract_expression(field), op.as_str()),
+		Filter::JsonContains(_, path) => format!("JSON_CONTAINS({field}, {PARAM_SYMBOL}, {})", path.sql_literal()),
+		Filter::JsonMemberOf(_, path) => format!("{PARAM_SYMBOL} MEMBER OF({})", path.extract_expression(field)),
+	}
+}
+
+fn subquery_binding_holder(subquery: &Subquery<RepoValue<'_>>) -> String {
+	let conditions = subquery.filters()
+		.map(|(field, filter)| filter_binding_holder(field, filter))
+		.collect();
+	subquery.select_expression(conditions)
+}
+
 /// returns "?, ?, ?, ..."
 fn comma_seperated_binding_holders(len: usize) -> String {
 	(0..len)
@@ -46,6 +66,17 @@ fn comma_seperated_binding_holders(len: usize) -> String {
 		.join(", ")
 }
 
+impl SqlxHelper for SqlOrder<'_> {
+	fn with_binding_holder(&self) -> String {
+		self.iter()
+			.map(|o| match o {
+				OrderBy::Field(field, direction) => format!("{field} {}", direction.as_str()),
+				OrderBy::Relevance(_, m, direction) => format!("{} {}", m.render(PARAM_SYMBOL), direction.as_str()),
+			})
+			.collect::<Vec<String>>().join(", ")
+	}
+}
+
 impl SqlxHelper for SqlValues<'_> {
 	fn with_binding_holder(&self) -> String {
 		self.iter()
@@ -58,7 +89,14 @@ impl SqlxHelper for SqlValues<'_> {
 
 impl<E> SqlxHelper for SqlUpdates<'_, E> {
 	fn with_binding_holder(&self) -> String {
-		self.dataset().with_binding_holder()
+		self.render(&mut |_, _| PARAM_SYMBOL.to_string())
+	}
+}
+
+impl<E> SqlxHelper for UpdateStatement<'_, E> {
+	/// returns the whole `UPDATE` statement, whose updates are bound before the filter
+	fn with_binding_holder(&self) -> String {
+		self.statement(self.updates().with_binding_holder(), self.filter().with_binding_holder())
 	}
 }
 
@@ -72,12 +110,14 @@ impl<'q, O> BindData<'q> for SqlxQueryAs<'q, O> {
 			RepoValue::Null => self.bind(None::<&str>),
 			RepoValue::Int(n) => self.bind(n),
 			RepoValue::UInt(u) => self.bind(u),
+			RepoValue::Float(f) => self.bind(f),
 			RepoValue::Double(f) => self.bind(f),
 			RepoValue::Date(d) => self.bind(d),
 			RepoValue::Time(t) => self.bind(t),
 			RepoValue::DateTime(dt) => self.bind(dt),
 			RepoValue::Str(s) => self.bind(s),
 			RepoValue::String(s) => self.bind(s),
+			RepoValue::Bytes(b) => self.bind(b),
 		}
 	}
 }
@@ -87,12 +127,14 @@ impl<'q> BindData<'q> for SqlxQuery<'q> {
 			RepoValue::Null => self.bind(None::<&str>),
 			RepoValue::Int(n) => self.bind(n),
 			RepoValue::UInt(u) => self.bind(u),
+			RepoValue::Float(f) => self.bind(f),
 			RepoValue::Double(f) => self.bind(f),
 			RepoValue::Date(d) => self.bind(d),
 			RepoValue::Time(t) => self.bind(t),
 			RepoValue::DateTime(dt) => self.bind(dt),
 			RepoValue::Str(s) => self.bind(s),
 			RepoValue::String(s) => self.bind(s),
+			RepoValue::Bytes(b) => self.bind(b),
 		}
 	}
 }
@@ -104,33 +146,88 @@ impl<'q, O> BindFilter<'q> for SqlxQueryAs<'q, O> {
 	fn bind_filter<'d: 'q>(self, filters: &'d SqlFilter<'_>) -> Self {
 		let mut q = self;
 		for f in filters.iter() {
-			q = match f.filter() {
-				Filter::Equal(data) => q.bind_data(data),
-				Filter::Not(data) => q.bind_data(data),
-				Filter::LessorThan(data) => q.bind_data(data),
-				Filter::EqualOrLessorThan(data) => q.bind_data(data),
-				Filter::GreaterThan(data) => q.bind_data(data),
-				Filter::EqualOrGreaterThan(data) => q.bind_data(data),
-				Filter::In(values) => {
-					let mut q = q;
-					for value in values.iter() {
-						q = q.bind_data(value);
-					}
-					q
-				},
-				Filter::NotIn(values) => {
-					let mut q = q;
-					for value in values.iter() {
-						q = q.bind_data(value);
-					}
-					q
-				},
-				Filter::Between(from, to) => {
-					q = q.bind_data(from);
-					q = q.bind_data(to);
-					q
-				},
-			};
+			q = bind_filter_data(q, f.filter());
+		}
+		q
+	}
+}
+
+impl<'q> BindFilter<'q> for SqlxQuery<'q> {
+	fn bind_filter<'d: 'q>(self, filters: &'d SqlFilter<'_>) -> Self {
+		let mut q = self;
+		for f in filters.iter() {
+			q = bind_filter_data(q, f.filter());
+		}
+		q
+	}
+}
+
+/// binds the values of `filter` in the order of their holders in `filter_binding_holder()`
+fn bind_filter_data<'q, 'd: 'q, Q: BindData<'q>>(q: Q, filter: &'d Filter<RepoValue<'_>>) -> Q {
+	match filter {
+		Filter::Equal(data) => q.bind_data(data),
+		Filter::Not(data) => q.bind_data(data),
+		Filter::LessorThan(data) => q.bind_data(data),
+		Filter::EqualOrLessorThan(data) => q.bind_data(data),
+		Filter::GreaterThan(data) => q.bind_data(data),
+		Filter::EqualOrGreaterThan(data) => q.bind_data(data),
+		Filter::In(values) => {
+			let mut q = q;
+			for value in values.iter() {
+				q = q.bind_data(value);
+			}
+			q
+		},
+		Filter::NotIn(values) => {
+			let mut q = q;
+			for value in values.iter() {
+				q = q.bind_data(value);
+			}
+			q
+		},
+		Filter::Between(from, to) => {
+			let q = q.bind_data(from);
+			q.bind_data(to)
+		},
+		Filter::IsNull | Filter::IsNotNull => q,
+		Filter::InSubquery(s) | Filter::NotInSubquery(s) | Filter::Exists(s) | Filter::NotExists(s) => {
+			let mut q = q;
+			for (_, filter) in s.filters() {
+				q = bind_filter_data(q, filter);
+			}
+			q
+		},
+		Filter::Compare(lhs, _, rhs) => {
+			let mut q = q;
+			for value in lhs.values().into_iter().chain(rhs.values()) {
+				q = q.bind_data(value);
+			}
+			q
+		},
+		Filter::Raw(raw) => {
+			let mut q = q;
+			for value in raw.params().iter() {
+				q = q.bind_data(value);
+			}
+			q
+		},
+		Filter::Match(m) => q.bind_data(m.query()),
+		Filter::JsonExtract(_, _, data) => q.bind_data(data),
+		Filter::JsonContains(data, _) => q.bind_data(data),
+		Filter::JsonMemberOf(data, _) => q.bind_data(data),
+	}
+}
+
+pub trait BindOrder<'q> {
+	fn bind_order<'d: 'q>(self, order: &'d SqlOrder<'_>) -> Self;
+}
+impl<'q, O> BindOrder<'q> for SqlxQueryAs<'q, O> {
+	fn bind_order<'d: 'q>(self, order: &'d SqlOrder<'_>) -> Self {
+		let mut q = self;
+		for o in order.iter() {
+			if let OrderBy::Relevance(_, m, _) = o {
+				q = q.bind_data(m.query());
+			}
 		}
 		q
 	}
@@ -151,3 +248,126 @@ impl<'q> BindValues<'q> for SqlxQuery<'q> {
 		q
 	}
 }
+
+pub trait BindUpdateStatement<'q> {
+	fn bind_update_statement<'d: 'q, E>(self, statement: &'d UpdateStatement<'_, E>) -> Self;
+}
+impl<'q> BindUpdateStatement<'q> for SqlxQuery<'q> {
+	/// binds the values of the updates, then those of the filter, in the order of `with_binding_holder()`
+	fn bind_update_statement<'d: 'q, E>(self, statement: &'d UpdateStatement<'_, E>) -> Self {
+		self.bind_values(statement.updates().dataset())
+			.bind_filter(statement.filter())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use sqlx::Execute;
+
+	use crate::{ArithmeticOp, Comparison, Direction, Expr, JsonPath, MatchMode, NamedFilterHolder, SqlFunction};
+	use super::*;
+
+	fn arguments<'q>(mut query: impl Execute<'q, sqlx::MySql>) -> String {
+		format!("{:?}", query.take_arguments())
+	}
+
+	/// the arguments of a query binding `values` in this order
+	fn bound(values: &[RepoValue<'_>]) -> String {
+		arguments(values.iter().fold(sqlx::query(""), |q, value| q.bind_data(value)))
+	}
+
+	fn placeholders(sql: &str) -> usize {
+		sql.matches(PARAM_SYMBOL).count()
+	}
+
+	#[test]
+	fn subqueries_bind_between_the_outer_filters() {
+		let customers = Subquery::new("customers", "id", SqlFilter::default()
+			.with("country", &Filter::Equal("FR"))
+			.with("vip", &Filter::Equal(1)));
+		let filter = SqlFilter::default()
+			.with("status", &Filter::Equal("open"))
+			.with("customer_id", &Filter::<RepoValue>::InSubquery(customers))
+			.with("total", &Filter::GreaterThan(10));
+
+		let sql = filter.with_binding_holder();
+		assert_eq!(sql, "status=? AND customer_id IN (SELECT id FROM customers WHERE country=? AND vip=?) AND total>?");
+		assert_eq!(placeholders(&sql), 4);
+		assert_eq!(arguments(sqlx::query_as::<_, (u64,)>(&sql).bind_filter(&filter)), bound(&["open".into(), "FR".into(), 1.into(), 10.into()]));
+	}
+
+	#[test]
+	fn comparisons_bind_the_left_values_first() {
+		let lhs = Expr::function(SqlFunction::Coalesce, vec![Expr::column("discount").unwrap(), Expr::value(RepoValue::from(0))]);
+		let rhs = Expr::Arithmetic(Box::new(Expr::column("price").unwrap()), ArithmeticOp::Multiply, Box::new(Expr::value(RepoValue::from(2))));
+		let filter = SqlFilter::default()
+			.with("status", &Filter::Equal("open"))
+			.with("discount", &Filter::Compare(lhs, Comparison::GreaterThan, rhs));
+
+		let sql = filter.with_binding_holder();
+		assert_eq!(sql, "status=? AND COALESCE(discount, ?)>(price * ?)");
+		assert_eq!(placeholders(&sql), 3);
+		assert_eq!(arguments(sqlx::query_as::<_, (u64,)>(&sql).bind_filter(&filter)), bound(&["open".into(), 0.into(), 2.into()]));
+	}
+
+	#[test]
+	fn raw_fragments_bind_in_place() {
+		let filter = SqlFilter::default()
+			.with("status", &Filter::Equal("open"))
+			.with_raw("price * quantity > ? AND price < ?", vec![100, 500]).unwrap()
+			.with("owner_id", &Filter::Equal(7));
+
+		let sql = filter.with_binding_holder();
+		assert_eq!(sql, "status=? AND (price * quantity > ? AND price < ?) AND owner_id=?");
+		assert_eq!(placeholders(&sql), 4);
+		assert_eq!(arguments(sqlx::query_as::<_, (u64,)>(&sql).bind_filter(&filter)), bound(&["open".into(), 100.into(), 500.into(), 7.into()]));
+	}
+
+	#[test]
+	fn relevance_binds_after_the_filter() {
+		let mut title = NamedFilterHolder::new("title");
+		title.matches("lamp", MatchMode::Boolean);
+		let filter = SqlFilter::default()
+			.with("status", &Filter::Equal("open"))
+			.with_named(&title);
+		let order = SqlOrder::default().with_relevance(&title, Direction::Desc);
+
+		let sql = format!("SELECT id FROM products WHERE {} ORDER BY {}", filter.with_binding_holder(), order.with_binding_holder());
+		assert_eq!(sql, "SELECT id FROM products WHERE status=? AND MATCH(title) AGAINST (? IN BOOLEAN MODE) \
+			ORDER BY MATCH(title) AGAINST (? IN BOOLEAN MODE) DESC");
+		assert_eq!(placeholders(&sql), 3);
+		let query = sqlx::query_as::<_, (u64,)>(&sql).bind_filter(&filter).bind_order(&order);
+		assert_eq!(arguments(query), bound(&["open".into(), "lamp".into(), "lamp".into()]));
+	}
+
+	#[test]
+	fn json_filters_bind_their_value_but_inline_their_path() {
+		let filter = SqlFilter::default()
+			.with("attributes", &Filter::JsonExtract(JsonPath::parse("$.size").unwrap(), Comparison::GreaterThan, 3))
+			.with("status", &Filter::Equal(1));
+
+		let sql = filter.with_binding_holder();
+		assert_eq!(sql, "JSON_EXTRACT(attributes, '$.size')>? AND status=?");
+		assert_eq!(placeholders(&sql), 2);
+		assert_eq!(arguments(sqlx::query_as::<_, (u64,)>(&sql).bind_filter(&filter)), bound(&[3.into(), 1.into()]));
+	}
+
+	#[test]
+	fn update_statements_bind_the_updates_before_the_filter() {
+		struct Order {
+			visits: i32,
+		}
+
+		let mut updates = SqlUpdates::<Order>::default();
+		updates.push("status", "closed", |_: &mut Order| {});
+		updates.push_default("note", |_: &mut Order| {});
+		updates.increment("visits", 1, |o: &mut Order| &mut o.visits);
+		let statement = UpdateStatement::new("orders", updates)
+			.with_filter(SqlFilter::default().with("id", &Filter::Equal(7)).with("status", &Filter::Equal("open")));
+
+		let sql = statement.with_binding_holder();
+		assert_eq!(sql, "UPDATE orders SET status=?, note=DEFAULT, visits=visits+? WHERE id=? AND status=?");
+		assert_eq!(placeholders(&sql), 4);
+		assert_eq!(arguments(sqlx::query(&sql).bind_update_statement(&statement)), bound(&["closed".into(), 1.into(), 7.into(), "open".into()]));
+	}
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use futures_core::stream::BoxStream;
+use sqlx::mysql::MySqlRow;
+
+use crate::{Audit, InsertResult, KeysetScan, Relation, RepoValue, SqlFilter, SqlValues, Table, TenantScope, TenantScopeError, UpdateResult, UpdateStatement, check_tenant_updates};
+use super::{BindFilter, ExecutorObject, SqlxHelper};
+
+/// An `ExecutorObject` restricted to the rows of a tenant: the tenant is added to the filters, the inserted values
+/// and the updates of the tenant scoped entities, and the entities of other tenants are rejected.
+/// Only the queries it can scope are offered, so that raw SQL cannot bypass the tenant.
+pub struct TenantExecutorObject<'s, 'a> {
+	executor_object: &'s mut ExecutorObject<'a>,
+	scope: TenantScope,
+}
+
+impl<'a> ExecutorObject<'a> {
+	pub fn with_tenant(&mut self, scope: TenantScope) -> TenantExecutorObject<'_, 'a> {
+		TenantExecutorObject { executor_object: self, scope }
+	}
+}
+
+/// The select of the rows of `E` within a tenant, rendered by `TenantExecutorObject::select_query()`.
+/// It owns the query and the parameters that the stream of `TenantExecutorObject::stream_entities()` borrows.
+pub struct TenantSelect<'f, E> {
+	query: String,
+	filter: SqlFilter<'f>,
+	entity: PhantomData<fn() -> E>,
+}
+
+impl<E> TenantSelect<'_, E> {
+	pub fn query(&self) -> &str {
+		&self.query
+	}
+}
+
+impl TenantExecutorObject<'_, '_> {
+	pub fn scope(&self) -> &TenantScope {
+		&self.scope
+	}
+
+	/// adds the tenant to `filter` if `E` is tenant scoped
+	pub fn filter<'f, E: Table>(&self, filter: SqlFilter<'f>) -> SqlFilter<'f> {
+		self.scope.filter::<E>(filter)
+	}
+
+	/// renders the select of the rows of `E` matching `filter` within the tenant
+	pub fn select_query<'f, E: Table>(&self, filter: SqlFilter<'f>) -> TenantSelect<'f, E> {
+		let filter = self.scope.filter::<E>(filter);
+		let query = if filter.is_empty() {
+			format!("SELECT {} FROM {}", E::TABLE_FIELDS, E::TABLE_NAME)
+		} else {
+			format!("SELECT {} FROM {} WHERE {}", E::TABLE_FIELDS, E::TABLE_NAME, filter.with_binding_holder())
+		};
+		TenantSelect { query, filter, entity: PhantomData }
+	}
+
+	/// selects the rows of `E` matching `filter` within the tenant
+	pub async fn select<E>(&mut self, filter: SqlFilter<'_>) -> Result<Vec<E>, sqlx::Error>
+	where E: Table + for<'r> sqlx::FromRow<'r, MySqlRow> + Send + Unpin {
+		let select = self.select_query::<E>(filter);
+		sqlx::query_as::<_, E>(&select.query)
+			.bind_filter(&select.filter)
+			.fetch_all(&mut *self.executor_object)
+			.await
+	}
+
+	/// streams the rows of `select`, which holds the connection until the stream is dropped.
+	///
+	/// ```ignore
+	/// let select = tenant_executor_object.select_query::<Order>(filter);
+	/// let mut orders = tenant_executor_object.stream_entities(&select);
+	/// ```
+	pub fn stream_entities<'e, 'q: 'e, E>(&'e mut self, select: &'q TenantSelect<'_, E>) -> BoxStream<'e, Result<E, sqlx::Error>>
+	where E: Table + for<'r> sqlx::FromRow<'r, MySqlRow> + Send + Unpin + 'e {
+		self.executor_object.fetch_stream(&select.query, &select.filter)
+	}
+
+	/// reads the next chunk of `scan` within the tenant, or `None` once the scan is over
+	pub async fn scan_chunk<E>(&mut self, scan: &mut KeysetScan<'_, E>) -> Result<Option<Vec<E>>, sqlx::Error>
+	where E: Table + for<'r> sqlx::FromRow<'r, MySqlRow> + Send + Unpin {
+		self.executor_object.scan_chunk_in_scope(scan, Some(&self.scope)).await
+	}
+
+	pub async fn insert_entity<E>(&mut self, values: SqlValues<'_>) -> Result<InsertResult, sqlx::Error>
+	where E: Table {
+		let values = self.scope.values::<E>(values).map_err(to_error)?;
+		self.executor_object.insert_entity_in_scope::<E>(&values, Some(&self.scope)).await
+	}
+
+	pub async fn upsert_entity<E>(&mut self, values: SqlValues<'_>) -> Result<UpdateResult, sqlx::Error>
+	where E: Table {
+		let values = self.scope.values::<E>(values).map_err(to_error)?;
+		self.executor_object.upsert_entity_in_scope::<E>(&values, Some(&self.scope)).await
+	}
+
+	pub async fn insert<E>(&mut self, entity: &mut E) -> Result<E::Key, sqlx::Error>
+	where E: Table, for<'e> SqlValues<'e>: From<&'e E> {
+		self.scope.check_entity(entity).map_err(to_error)?;
+		self.executor_object.insert_in_scope(entity, Some(&self.scope)).await
+	}
+
+	pub async fn insert_auto_increment<E>(&mut self, values: SqlValues<'_>) -> Result<E::Key, sqlx::Error>
+	where E: Table, E::Key: TryFrom<u64> {
+		let values = self.scope.values::<E>(values).map_err(to_error)?;
+		self.executor_object.insert_auto_increment_in_scope::<E>(&values, Some(&self.scope)).await
+	}
+
+	pub async fn exec_update_statement<E>(&mut self, statement: UpdateStatement<'_, E>, target: &mut E) -> Result<UpdateResult, sqlx::Error>
+	where E: Table {
+		self.scope.check_entity(target)
+			.and_then(|_| check_tenant_updates(statement.updates()))
+			.map_err(to_error)?;
+		self.executor_object.exec_update_statement(self.scope.statement(statement), target).await
+	}
+
+	pub async fn exec_audited_update<E>(&mut self, statement: UpdateStatement<'_, E>, target: &mut E, audit: Audit<'_>) -> Result<UpdateResult, sqlx::Error>
+	where E: Table {
+		self.scope.check_entity(target)
+			.and_then(|_| check_tenant_updates(statement.updates()))
+			.map_err(to_error)?;
+		self.executor_object.exec_audited_update(self.scope.statement(statement), target, audit).await
+	}
+
+	pub async fn delete_entity<E>(&mut self, entity: &mut E) -> Result<UpdateResult, sqlx::Error>
+	where E: Table, E::Key: Clone + 'static {
+		self.scope.check_entity(entity).map_err(to_error)?;
+		self.executor_object.delete_entity_in_scope(entity, Some(&self.scope)).await
+	}
+
+	pub async fn restore_entity<E>(&mut self, entity: &mut E) -> Result<UpdateResult, sqlx::Error>
+	where E: Table, E::Key: Clone + 'static {
+		self.scope.check_entity(entity).map_err(to_error)?;
+		self.executor_object.restore_entity_in_scope(entity, Some(&self.scope)).await
+	}
+
+	pub async fn load_related<P, C, K>(&mut self, relation: &Relation<P, C, K>, parents: &[P]) -> Result<HashMap<K, Vec<C>>, sqlx::Error>
+	where
+		C: Table + for<'r> sqlx::FromRow<'r, MySqlRow> + Send + Unpin,
+		K: Into<RepoValue<'static>> + Eq + Hash + Clone,
+	{
+		self.executor_object.load_related_in_scope(relation, parents, Some(&self.scope)).await
+	}
+}
+
+fn to_error(e: TenantScopeError) -> sqlx::Error {
+	sqlx::Error::Decode(Box::new(e))
+}
@@ -0,0 +1,134 @@
+use sqlx::MySqlPool;
+use sqlx::mysql::MySqlDatabaseError;
+use tokio::sync::Mutex;
+use futures_core::future::BoxFuture;
+
+use crate::{TransactionOptions, RetryPolicy, RetriesExhaustedError, is_transient_error_code, is_transient_boxed_error};
+use super::ExecutorObject;
+
+type SqlxTransaction<'t> = sqlx::Transaction<'t, sqlx::MySql>;
+
+/// Begins the transactions on the connections of a pool.
+#[derive(Clone)]
+pub struct TransactionManager {
+	pool: MySqlPool,
+	options: TransactionOptions,
+	retry_policy: RetryPolicy,
+}
+
+impl TransactionManager {
+	pub fn new(pool: MySqlPool) -> Self {
+		Self { pool, options: TransactionOptions::default(), retry_policy: RetryPolicy::default() }
+	}
+
+	pub fn with_options(mut self, options: TransactionOptions) -> Self {
+		self.options = options;
+		self
+	}
+
+	pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+		self.retry_policy = retry_policy;
+		self
+	}
+
+	pub fn pool(&self) -> &MySqlPool {
+		&self.pool
+	}
+
+	pub fn options(&self) -> TransactionOptions {
+		self.options
+	}
+
+	pub async fn begin(&self) -> Result<UnitOfWork, sqlx::Error> {
+		let mut conn = self.pool.acquire().await?;
+		// applies to the next transaction of the connection only
+		if let Some(statement) = self.options.set_transaction_statement() {
+			sqlx::query(&statement).execute(&mut *conn).await?;
+		}
+		let tx = SqlxTransaction::begin(conn).await?;
+		Ok(UnitOfWork { tx: Mutex::new(tx) })
+	}
+
+	/// runs `f` in a new transaction, which is committed if `f` succeeds and rolled back otherwise.
+	/// If `f` panics, the transaction is rolled back when dropped.
+	///
+	/// ```ignore
+	/// let account = manager.transaction(|tx| Box::pin(async move {
+	///     tx.exec_update_statement(statement, &mut account).await?;
+	///     Ok(account)
+	/// })).await?;
+	/// ```
+	pub async fn transaction<T, F>(&self, f: F) -> Result<T, sqlx::Error>
+	where F: for<'t, 'e> FnOnce(&'t mut ExecutorObject<'e>) -> BoxFuture<'t, Result<T, sqlx::Error>> {
+		let uow = self.begin().await?;
+		let result = {
+			let mut executor = uow.executor_object().await;
+			f(&mut executor).await
+		};
+
+		match result {
+			Ok(value) => {
+				uow.commit().await?;
+				Ok(value)
+			},
+			Err(e) => {
+				// the error of `f` matters more than the one of the rollback
+				let _ = uow.rollback().await;
+				Err(e)
+			},
+		}
+	}
+
+	/// runs `f` in a new transaction like `transaction()`, and runs it again in another transaction
+	/// as long as it fails with a transient error, as told by `is_transient_error()`, and the retry policy allows it.
+	/// When no attempt is left, the last error is returned in a `RetriesExhaustedError`.
+	pub async fn transaction_with_retry<T, F>(&self, f: F) -> Result<T, sqlx::Error>
+	where F: for<'t, 'e> Fn(&'t mut ExecutorObject<'e>) -> BoxFuture<'t, Result<T, sqlx::Error>> {
+		let mut attempt = 1;
+		loop {
+			match self.transaction(&f).await {
+				Err(e) if is_transient_error(&e) => {
+					if attempt >= self.retry_policy.max_attempts() {
+						return Err(sqlx::Error::Decode(Box::new(RetriesExhaustedError::new(attempt, Box::new(e)))));
+					}
+					tokio::time::sleep(self.retry_policy.backoff(attempt)).await;
+					attempt += 1;
+				},
+				result => return result,
+			}
+		}
+	}
+}
+
+/// returns whether the transaction may succeed if run again after `error`,
+/// like `RepoError::from(error).is_transient()`: a deadlock, a timeout of a lock wait, of a statement or of the pool
+pub fn is_transient_error(error: &sqlx::Error) -> bool {
+	match error {
+		sqlx::Error::Database(e) => e.try_downcast_ref::<MySqlDatabaseError>()
+			.is_some_and(|e| is_transient_error_code(e.number())),
+		sqlx::Error::PoolTimedOut => true,
+		sqlx::Error::Decode(e) => is_transient_boxed_error(&**e),
+		_ => false,
+	}
+}
+
+/// A transaction in progress, which hands out `ExecutorObject::MutexGuardTransaction` handles until it is committed or rolled back.
+/// Dropping it without committing rolls the transaction back.
+pub struct UnitOfWork {
+	tx: Mutex<SqlxTransaction<'static>>,
+}
+
+impl UnitOfWork {
+	/// waits for the previous handle, if any, to be dropped
+	pub async fn executor_object(&self) -> ExecutorObject<'_> {
+		ExecutorObject::MutexGuardTransaction(self.tx.lock().await)
+	}
+
+	pub async fn commit(self) -> Result<(), sqlx::Error> {
+		self.tx.into_inner().commit().await
+	}
+
+	pub async fn rollback(self) -> Result<(), sqlx::Error> {
+		self.tx.into_inner().rollback().await
+	}
+}
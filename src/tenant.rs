@@ -0,0 +1,223 @@
+use crate::{Filter, RepoValue, SqlFilter, SqlUpdates, SqlValues, Table, TenantScopeError, UpdateStatement};
+
+/// The tenant whose rows a repository may see and change, of the same type as the tenant columns.
+/// It is applied to the entities with a tenant column only, and the others are left as they are.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TenantScope(RepoValue<'static>);
+
+impl TenantScope {
+	pub fn new(tenant: impl Into<RepoValue<'static>>) -> Self {
+		Self(tenant.into())
+	}
+
+	pub fn tenant(&self) -> &RepoValue<'static> {
+		&self.0
+	}
+
+	/// adds `{tenant column} = {tenant}` to `filter`, replacing the conditions on the tenant column if any,
+	/// so that the filter never reaches the rows of another tenant
+	pub fn filter<'a, E: Table>(&self, filter: SqlFilter<'a>) -> SqlFilter<'a> {
+		let Some(field) = E::TENANT_FIELD else {
+			return filter;
+		};
+		let mut scoped = SqlFilter::default().with(field, &Filter::<RepoValue<'a>>::Equal(self.0.clone()));
+		scoped.extend(filter.into_iter().filter(|f| f.name() != field));
+		scoped
+	}
+
+	/// sets the tenant column of the inserted `values` to the tenant, failing if they have another tenant
+	pub fn values<'a, E: Table>(&self, mut values: SqlValues<'a>) -> Result<SqlValues<'a>, TenantScopeError> {
+		if let Some(field) = E::TENANT_FIELD {
+			let same_tenant = values.iter()
+				.find(|(f, _)| *f == field)
+				.map(|(_, tenant)| self.is_tenant(tenant));
+			match same_tenant {
+				Some(false) => return Err(TenantScopeError::Mismatch(E::ENTITY_NAME)),
+				Some(true) => {},
+				None => values.push(field, self.0.clone()),
+			}
+		}
+		Ok(values)
+	}
+
+	/// restricts `statement` to the rows of the tenant
+	pub fn statement<'a, E: Table>(&self, statement: UpdateStatement<'a, E>) -> UpdateStatement<'a, E> {
+		statement.map_filter(|filter| self.filter::<E>(filter)).scoped()
+	}
+
+	/// fails if `entity` belongs to another tenant
+	pub fn check_entity<E: Table>(&self, entity: &E) -> Result<(), TenantScopeError> {
+		match E::TENANT_FIELD.and_then(|field| entity.field_value(field)) {
+			Some(tenant) if !self.is_tenant(&tenant) => Err(TenantScopeError::Mismatch(E::ENTITY_NAME)),
+			_ => Ok(()),
+		}
+	}
+
+	/// compares `value` with the tenant whatever the variant holding them,
+	/// since a signed or borrowed value may stand for an unsigned or owned tenant
+	fn is_tenant(&self, value: &RepoValue<'_>) -> bool {
+		match (&self.0, value) {
+			(RepoValue::Int(_) | RepoValue::UInt(_), RepoValue::Int(_) | RepoValue::UInt(_)) => as_integer(&self.0) == as_integer(value),
+			(RepoValue::Str(_) | RepoValue::String(_), RepoValue::Str(_) | RepoValue::String(_)) => as_str(&self.0) == as_str(value),
+			(tenant, value) => tenant == value,
+		}
+	}
+}
+
+fn as_integer(value: &RepoValue<'_>) -> Option<i128> {
+	match value {
+		RepoValue::Int(v) => Some(i128::from(*v)),
+		RepoValue::UInt(v) => Some(i128::from(*v)),
+		_ => None,
+	}
+}
+
+fn as_str<'v>(value: &'v RepoValue<'_>) -> Option<&'v str> {
+	match value {
+		RepoValue::Str(v) => Some(v),
+		RepoValue::String(v) => Some(v.as_str()),
+		_ => None,
+	}
+}
+
+/// fails if a tenant scoped entity is reached without a scope
+pub fn check_tenant_scope<E: Table>(scope: Option<&TenantScope>) -> Result<(), TenantScopeError> {
+	match (E::TENANT_FIELD, scope) {
+		(Some(_), None) => Err(TenantScopeError::Missing(E::ENTITY_NAME)),
+		_ => Ok(()),
+	}
+}
+
+/// fails if `filter` does not restrict a tenant scoped entity to a tenant
+pub fn check_tenant_filter<E: Table>(filter: &SqlFilter<'_>) -> Result<(), TenantScopeError> {
+	match E::TENANT_FIELD {
+		Some(field) if !filter.iter().any(|f| f.name() == field) => Err(TenantScopeError::Missing(E::ENTITY_NAME)),
+		_ => Ok(()),
+	}
+}
+
+/// fails if the inserted `values` of a tenant scoped entity have no tenant
+pub fn check_tenant_values<E: Table>(values: &SqlValues<'_>) -> Result<(), TenantScopeError> {
+	match E::TENANT_FIELD {
+		Some(field) if !values.iter().any(|(f, _)| *f == field) => Err(TenantScopeError::Missing(E::ENTITY_NAME)),
+		_ => Ok(()),
+	}
+}
+
+/// fails if `updates` move a row of a tenant scoped entity to another tenant
+pub fn check_tenant_updates<E: Table>(updates: &SqlUpdates<'_, E>) -> Result<(), TenantScopeError> {
+	match E::TENANT_FIELD {
+		Some(field) if updates.assigns(field) => Err(TenantScopeError::Reassigned(E::ENTITY_NAME)),
+		_ => Ok(()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::repo_entity;
+	use super::*;
+
+	repo_entity!(
+		#[derive(Debug, Clone, PartialEq)]
+		#[table_name = "invoices"]
+		#[tenant = tenant_id]
+		struct Invoice {
+			keys { id: u64 },
+			data { tenant_id: u64, total: i64 }
+		}
+	);
+
+	repo_entity!(
+		#[derive(Debug, Clone, PartialEq)]
+		#[table_name = "folders"]
+		#[tenant = org]
+		struct Folder {
+			keys { id: u64 },
+			data { org: String, name: String }
+		}
+	);
+
+	repo_entity!(
+		#[derive(Debug, Clone, PartialEq)]
+		#[table_name = "notes"]
+		struct Note {
+			keys { id: u64 },
+			data { text: String }
+		}
+	);
+
+	fn set_total(total: i64) -> SqlUpdates<'static, Invoice> {
+		let mut updates = SqlUpdates::default();
+		updates.push("total", total, move |invoice: &mut Invoice| invoice.total = total);
+		updates
+	}
+
+	#[test]
+	fn values_accept_the_tenant_in_any_integer_variant() {
+		let scope = TenantScope::new(7i64);
+		assert!(scope.values::<Invoice>(SqlValues::default().with("tenant_id", 7u64)).is_ok());
+		assert_eq!(scope.values::<Invoice>(SqlValues::default().with("tenant_id", 8u64)).err(), Some(TenantScopeError::Mismatch("Invoice")));
+
+		let values = scope.values::<Invoice>(SqlValues::default().with("total", 3i64)).unwrap();
+		assert_eq!(values.expressions(), "total=3, tenant_id=7");
+	}
+
+	#[test]
+	fn values_accept_the_tenant_in_any_string_variant() {
+		let scope = TenantScope::new("acme".to_string());
+		assert!(scope.values::<Folder>(SqlValues::default().with("org", "acme")).is_ok());
+		assert_eq!(scope.values::<Folder>(SqlValues::default().with("org", "other")).err(), Some(TenantScopeError::Mismatch("Folder")));
+	}
+
+	#[test]
+	fn entities_are_compared_with_the_tenant_whatever_the_variant() {
+		let invoice = Invoice { id: 1, tenant_id: 7, total: 10 };
+		assert!(TenantScope::new(7i64).check_entity(&invoice).is_ok());
+		assert_eq!(TenantScope::new(-7i64).check_entity(&invoice), Err(TenantScopeError::Mismatch("Invoice")));
+
+		let folder = Folder { id: 1, org: "acme".to_string(), name: "docs".to_string() };
+		assert!(TenantScope::new("acme").check_entity(&folder).is_ok());
+		assert_eq!(TenantScope::new(7u64).check_entity(&folder), Err(TenantScopeError::Mismatch("Folder")));
+	}
+
+	#[test]
+	fn filters_are_restricted_to_the_tenant() {
+		let scope = TenantScope::new(7u64);
+		let filter = || SqlFilter::default()
+			.with("tenant_id", &Filter::Equal(9u64))
+			.with("total", &Filter::GreaterThan(1i64));
+		assert_eq!(scope.filter::<Invoice>(filter()).expressions(), "tenant_id=7, total>1");
+		assert_eq!(scope.filter::<Note>(filter()).expressions(), filter().expressions());
+	}
+
+	#[test]
+	fn tenant_entities_need_a_scope() {
+		let scope = TenantScope::new(7u64);
+		assert_eq!(check_tenant_scope::<Invoice>(None), Err(TenantScopeError::Missing("Invoice")));
+		assert!(check_tenant_scope::<Invoice>(Some(&scope)).is_ok());
+		assert!(check_tenant_scope::<Note>(None).is_ok());
+	}
+
+	#[test]
+	fn update_statements_of_tenant_entities_need_a_scope() {
+		let invoice = Invoice { id: 1, tenant_id: 7, total: 10 };
+		let scope = TenantScope::new(7u64);
+		let statement = UpdateStatement::for_entity(&invoice, set_total(20));
+		assert_eq!(statement.check_tenant(), Err(TenantScopeError::Missing("Invoice")));
+
+		let statement = scope.statement(statement);
+		assert!(statement.check_tenant().is_ok());
+		assert_eq!(statement.expressions(), "UPDATE invoices SET total=20 WHERE tenant_id=7 AND id=1");
+	}
+
+	#[test]
+	fn updates_may_not_move_a_row_to_another_tenant() {
+		let invoice = Invoice { id: 1, tenant_id: 7, total: 10 };
+		let mut updates = set_total(20);
+		updates.push("tenant_id", 8u64, |invoice: &mut Invoice| invoice.tenant_id = 8);
+		assert_eq!(check_tenant_updates(&updates), Err(TenantScopeError::Reassigned("Invoice")));
+
+		let statement = TenantScope::new(7u64).statement(UpdateStatement::for_entity(&invoice, updates));
+		assert_eq!(statement.check_tenant(), Err(TenantScopeError::Reassigned("Invoice")));
+	}
+}
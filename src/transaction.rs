@@ -0,0 +1,208 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// The isolation level of a transaction, as in `SET TRANSACTION ISOLATION LEVEL ...`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsolationLevel {
+	ReadUncommitted,
+	ReadCommitted,
+	RepeatableRead,
+	Serializable,
+}
+
+impl IsolationLevel {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			IsolationLevel::ReadUncommitted => "READ UNCOMMITTED",
+			IsolationLevel::ReadCommitted => "READ COMMITTED",
+			IsolationLevel::RepeatableRead => "REPEATABLE READ",
+			IsolationLevel::Serializable => "SERIALIZABLE",
+		}
+	}
+}
+
+/// How the transactions are started; by default, with the isolation level of the server and in read-write mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TransactionOptions {
+	isolation_level: Option<IsolationLevel>,
+	read_only: bool,
+}
+
+impl TransactionOptions {
+	pub fn with_isolation_level(mut self, level: IsolationLevel) -> Self {
+		self.isolation_level = Some(level);
+		self
+	}
+
+	pub fn read_only(mut self) -> Self {
+		self.read_only = true;
+		self
+	}
+
+	pub fn isolation_level(&self) -> Option<IsolationLevel> {
+		self.isolation_level
+	}
+
+	pub fn is_read_only(&self) -> bool {
+		self.read_only
+	}
+
+	/// returns the `SET TRANSACTION` statement applying the options to the next transaction, if any option is set
+	pub fn set_transaction_statement(&self) -> Option<String> {
+		let mut characteristics = Vec::new();
+		if let Some(level) = self.isolation_level {
+			characteristics.push(format!("ISOLATION LEVEL {}", level.as_str()));
+		}
+		if self.read_only {
+			characteristics.push(String::from("READ ONLY"));
+		}
+
+		if characteristics.is_empty() {
+			None
+		} else {
+			Some(format!("SET TRANSACTION {}", characteristics.join(", ")))
+		}
+	}
+}
+
+/// `ER_LOCK_DEADLOCK`, after which the server has rolled the transaction back
+pub const ER_LOCK_DEADLOCK: u16 = 1213;
+/// `ER_LOCK_WAIT_TIMEOUT`
+pub const ER_LOCK_WAIT_TIMEOUT: u16 = 1205;
+/// `ER_QUERY_TIMEOUT`, when a statement is interrupted by its maximum execution time
+pub const ER_QUERY_TIMEOUT: u16 = 3024;
+
+/// returns whether a transaction failing with the server error `code` may succeed if run again.
+/// `RepoError::from_server_error()` classifies these codes as `RepoError::Deadlock` or `RepoError::Timeout`.
+pub fn is_transient_error_code(code: u16) -> bool {
+	matches!(code, ER_LOCK_DEADLOCK | ER_LOCK_WAIT_TIMEOUT | ER_QUERY_TIMEOUT)
+}
+
+/// How many times a transaction failing with a transient error is run, and how long to wait in between.
+/// The wait doubles after every attempt, up to `max_backoff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+	max_attempts: u32,
+	initial_backoff: Duration,
+	max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+	fn default() -> Self {
+		Self { max_attempts: 3, initial_backoff: Duration::from_millis(50), max_backoff: Duration::from_secs(1) }
+	}
+}
+
+impl RetryPolicy {
+	/// `max_attempts` counts the first run, so that 1 disables the retries
+	pub fn new(max_attempts: u32) -> Self {
+		assert!(max_attempts > 0, "a transaction needs at least one attempt");
+		Self { max_attempts, ..Self::default() }
+	}
+
+	pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+		self.initial_backoff = initial;
+		self.max_backoff = max;
+		self
+	}
+
+	pub fn max_attempts(&self) -> u32 {
+		self.max_attempts
+	}
+
+	/// returns the wait after the failure of the `attempt`-th run, counted from 1
+	pub fn backoff(&self, attempt: u32) -> Duration {
+		let factor = 1u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+		self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+	}
+}
+
+static SAVEPOINT_SEQUENCE: AtomicUsize = AtomicUsize::new(0);
+
+/// A savepoint of a nested transaction, named `sp_{n}` with `n` unique in the process,
+/// so that the savepoints of a transaction never collide however deep they are nested.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Savepoint(String);
+
+impl Savepoint {
+	pub fn new() -> Self {
+		let n = SAVEPOINT_SEQUENCE.fetch_add(1, Ordering::Relaxed) + 1;
+		Self(format!("sp_{n}"))
+	}
+
+	pub fn name(&self) -> &str {
+		&self.0
+	}
+
+	pub fn create_statement(&self) -> String {
+		format!("SAVEPOINT {}", self.0)
+	}
+
+	pub fn rollback_statement(&self) -> String {
+		format!("ROLLBACK TO SAVEPOINT {}", self.0)
+	}
+
+	pub fn release_statement(&self) -> String {
+		format!("RELEASE SAVEPOINT {}", self.0)
+	}
+}
+
+impl Default for Savepoint {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn default_options_set_nothing() {
+		assert_eq!(TransactionOptions::default().set_transaction_statement(), None);
+	}
+
+	#[test]
+	fn options_are_set_in_one_statement() {
+		let options = TransactionOptions::default()
+			.with_isolation_level(IsolationLevel::Serializable)
+			.read_only();
+		assert_eq!(options.set_transaction_statement().as_deref(), Some("SET TRANSACTION ISOLATION LEVEL SERIALIZABLE, READ ONLY"));
+		assert_eq!(
+			TransactionOptions::default().with_isolation_level(IsolationLevel::ReadCommitted).set_transaction_statement().as_deref(),
+			Some("SET TRANSACTION ISOLATION LEVEL READ COMMITTED"),
+		);
+	}
+
+	#[test]
+	fn savepoints_have_distinct_names() {
+		let outer = Savepoint::new();
+		let inner = Savepoint::new();
+		assert_ne!(outer.name(), inner.name());
+		assert_eq!(inner.create_statement(), format!("SAVEPOINT {}", inner.name()));
+		assert_eq!(inner.rollback_statement(), format!("ROLLBACK TO SAVEPOINT {}", inner.name()));
+		assert_eq!(inner.release_statement(), format!("RELEASE SAVEPOINT {}", inner.name()));
+	}
+
+	#[test]
+	fn backoff_doubles_up_to_the_maximum() {
+		let policy = RetryPolicy::new(5).with_backoff(Duration::from_millis(10), Duration::from_millis(50));
+		let backoffs = (1..=5).map(|attempt| policy.backoff(attempt).as_millis()).collect::<Vec<_>>();
+		assert_eq!(backoffs, [10, 20, 40, 50, 50]);
+		assert_eq!(policy.backoff(100), Duration::from_millis(50));
+	}
+
+	#[test]
+	fn only_deadlocks_and_timeouts_are_transient() {
+		assert!(is_transient_error_code(ER_LOCK_DEADLOCK));
+		assert!(is_transient_error_code(ER_LOCK_WAIT_TIMEOUT));
+		assert!(is_transient_error_code(ER_QUERY_TIMEOUT));
+		assert!(!is_transient_error_code(1062));
+	}
+
+	#[test]
+	#[should_panic(expected = "at least one attempt")]
+	fn retry_policies_need_an_attempt() {
+		RetryPolicy::new(0);
+	}
+}
@@ -1,14 +1,120 @@
 use std::fmt::Debug;
 
-use crate::errors::EntityNotFoundError;
+use crate::errors::{DuplicateError, EntityNotFoundError, OptimisticLockError};
+use crate::{RepoValue, SqlFilter, SqlUpdates};
 
 pub trait Entity: Send {
 	type Key: Debug + Send + Sync;
 
 	fn get_key(&self) -> Self::Key;
-	fn not_found(key: Self::Key) -> EntityNotFoundError<Self::Key>;
+	fn not_found(key: Self::Key) -> EntityNotFoundError;
 }
 
 pub trait AsStaticStr {
 	fn as_str(&self) -> &'static str;
 }
+
+pub trait Table: Entity {
+	const TABLE_NAME: &'static str;
+	const TABLE_FIELDS: &'static str;
+	const KEY_FIELDS: &'static str;
+	/// the name of the entity in audit records
+	const ENTITY_NAME: &'static str = Self::TABLE_NAME;
+
+	/// the column incremented on every update, to detect lost updates
+	const VERSION_FIELD: Option<&'static str> = None;
+	/// the timestamp column of a soft delete; rows where it is not NULL are deleted
+	const SOFT_DELETE_FIELD: Option<&'static str> = None;
+	/// the column of the tenant owning each row, to which every query on the entity must be restricted
+	const TENANT_FIELD: Option<&'static str> = None;
+	/// the timestamp column set once when the row is inserted, and never updated
+	const CREATED_AT_FIELD: Option<&'static str> = None;
+	/// the fields of each unique key besides the primary key
+	const UNIQUE_KEYS: &'static [&'static [&'static str]] = &[];
+
+	/// returns the current value of the column `field`, or `None` if the entity has no such column
+	fn field_value(&self, _field: &str) -> Option<RepoValue<'static>> {
+		None
+	}
+
+	/// returns the filter selecting the row of this entity
+	fn key_filter(&self) -> SqlFilter<'static>;
+
+	/// returns the filter guarding an update of this entity, which also matches the version if any
+	fn update_filter(&self) -> SqlFilter<'static> {
+		self.key_filter()
+	}
+
+	/// sets the timestamps of this new entity that are still NULL, before it is inserted
+	fn touch_inserted(&mut self) {}
+
+	/// adds the assignments implied by every update of this entity, like the increment of the version
+	fn touch_updates(_updates: &mut SqlUpdates<'_, Self>) where Self: Sized {}
+
+	/// adds the assignments marking this entity as deleted, if it has a soft delete column
+	fn mark_deleted(_updates: &mut SqlUpdates<'_, Self>) where Self: Sized {}
+
+	/// adds the assignments clearing the soft delete of this entity, if it has a soft delete column
+	fn mark_restored(_updates: &mut SqlUpdates<'_, Self>) where Self: Sized {}
+
+	fn lost_update(key: Self::Key) -> OptimisticLockError;
+
+	/// returns the error of a duplicate entry on the unique index `index`, naming the fields of the matching unique key
+	fn duplicate(index: Option<&str>) -> DuplicateError {
+		let fields = unique_key_fields(Self::KEY_FIELDS, Self::UNIQUE_KEYS, index);
+		DuplicateError::new(Self::ENTITY_NAME, fields, index.map(String::from))
+	}
+}
+
+/// matches `index` with `PRIMARY`, with the name MySQL gives a unique key by default, which is its first field,
+/// or with a name containing the fields joined by `_`, like `uk_tenant_id_slug`, preferring the longest
+fn unique_key_fields(key_fields: &'static str, unique_keys: &[&'static [&'static str]], index: Option<&str>) -> Vec<&'static str> {
+	let Some(index) = index else {
+		return Vec::new();
+	};
+	if index.eq_ignore_ascii_case("PRIMARY") {
+		return key_fields.split(", ").collect();
+	}
+
+	let index = index.to_ascii_lowercase();
+	if let Some(fields) = unique_keys.iter().find(|fields| fields.first().is_some_and(|f| *f == index)) {
+		return fields.to_vec();
+	}
+	unique_keys.iter()
+		.filter(|fields| index.contains(&fields.join("_")))
+		.max_by_key(|fields| fields.len())
+		.map(|fields| fields.to_vec())
+		.unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::repo_entity;
+	use super::*;
+
+	repo_entity!(
+		#[table_name = "users"]
+		#[unique(email)]
+		#[unique(tenant_id, slug)]
+		struct User {
+			keys { id: u64 },
+			data { tenant_id: u64, slug: String, email: String }
+		}
+	);
+
+	#[test]
+	fn duplicate_names_the_fields_of_the_violated_key() {
+		assert_eq!(User::duplicate(Some("PRIMARY")).fields(), ["id"]);
+		assert_eq!(User::duplicate(Some("email")).fields(), ["email"]);
+		assert_eq!(User::duplicate(Some("uk_tenant_id_slug")).fields(), ["tenant_id", "slug"]);
+		assert_eq!(User::duplicate(Some("uk_tenant_id_slug")).index(), Some("uk_tenant_id_slug"));
+	}
+
+	#[test]
+	fn duplicate_of_an_unknown_index_names_no_field() {
+		let duplicate = User::duplicate(Some("idx_other"));
+		assert!(duplicate.fields().is_empty());
+		assert_eq!(duplicate.to_string(), "Duplicate User {index=idx_other}");
+		assert!(User::duplicate(None).fields().is_empty());
+	}
+}